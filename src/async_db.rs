@@ -0,0 +1,142 @@
+use super::{
+    db::{CaseSensitivity, Hkvdb},
+    error::Error,
+    key::Key,
+    table::Writeable,
+    value::Value,
+};
+use std::collections::HashMap;
+
+/// Wraps a database handle so that its operations can be called from async code.
+///
+/// Each operation runs on Tokio's blocking thread pool, so it doesn't block the async runtime.
+pub struct HkvdbAsync<M, V, K = u64> {
+    db: Hkvdb<M, V, K>,
+}
+
+impl<M, V, K> Clone for HkvdbAsync<M, V, K> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+        }
+    }
+}
+
+impl<M, V, K> From<Hkvdb<M, V, K>> for HkvdbAsync<M, V, K> {
+    fn from(db: Hkvdb<M, V, K>) -> Self {
+        Self::new(db)
+    }
+}
+
+impl<M, V, K> HkvdbAsync<M, V, K> {
+    pub fn new(db: Hkvdb<M, V, K>) -> Self {
+        Self { db }
+    }
+
+    pub fn inner(&self) -> &Hkvdb<M, V, K> {
+        &self.db
+    }
+}
+
+impl<M: Send + Sync + 'static, V: Send + 'static, K: Send + 'static> HkvdbAsync<M, V, K> {
+    async fn run<
+        T: Send + 'static,
+        F: FnOnce(&Hkvdb<M, V, K>) -> Result<T, Error> + Send + 'static,
+    >(
+        &self,
+        f: F,
+    ) -> Result<T, Error> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || f(&db)).await?
+    }
+}
+
+impl<M: Send + Sync + 'static, V: Value + Send + 'static, K: Key + Send + 'static>
+    HkvdbAsync<M, V, K>
+{
+    pub async fn get(&self, id: K) -> Result<HashMap<String, V>, Error> {
+        self.run(move |db| db.get(id)).await
+    }
+
+    pub async fn get_one(&self, id: K, data: &str) -> Result<Option<V>, Error> {
+        let data = data.to_string();
+        self.run(move |db| db.get_one(id, &data)).await
+    }
+}
+
+impl<V: Value + Send + 'static, K: Key + Send + 'static> HkvdbAsync<Writeable, V, K> {
+    pub async fn put<IV: Into<V> + Send + 'static>(
+        &self,
+        id: K,
+        data: &str,
+        value: IV,
+    ) -> Result<(), Error> {
+        let data = data.to_string();
+        self.run(move |db| db.put(id, &data, value)).await
+    }
+
+    pub async fn merge_value(&self, id: K, data: &str, value: V) -> Result<(), Error> {
+        let data = data.to_string();
+        self.run(move |db| db.merge_value(id, &data, value)).await
+    }
+
+    pub async fn put_batch<
+        S: AsRef<str> + Send + 'static,
+        IV: Into<V> + Send + 'static,
+        I: IntoIterator<Item = (K, S, IV)> + Send + 'static,
+    >(
+        &self,
+        batch: I,
+    ) -> Result<(), Error> {
+        self.run(move |db| db.put_batch(batch)).await
+    }
+}
+
+impl<M: Send + Sync + 'static, V: Send + 'static> HkvdbAsync<M, V> {
+    pub async fn search(&self, data: &str) -> Result<Vec<u64>, Error> {
+        let data = data.to_string();
+        self.run(move |db| db.search(&data)).await
+    }
+
+    pub async fn search_ci(&self, data: &str) -> Result<Vec<u64>, Error> {
+        let data = data.to_string();
+        self.run(move |db| db.search_ci(&data)).await
+    }
+}
+
+impl<V: Value + Send + 'static> HkvdbAsync<Writeable, V> {
+    pub async fn make_index(&self, case_sensitivity: CaseSensitivity) -> Result<(), Error> {
+        self.run(move |db| db.make_index(case_sensitivity)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::value::Range32;
+    use super::*;
+
+    #[tokio::test]
+    async fn put_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: HkvdbAsync<Writeable, Range32> = Hkvdb::new(dir, false).unwrap().into();
+
+        db.put(1, "foo", 101).await.unwrap();
+        db.put(1, "foo", 23).await.unwrap();
+        db.put(1, "bar", 1).await.unwrap();
+        db.put(2, "foo", 23).await.unwrap();
+
+        let expected = vec![
+            ("foo".to_string(), (23, 101).into()),
+            ("bar".to_string(), (1, 1).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).await.unwrap(), expected);
+        assert_eq!(db.get_one(2, "foo").await.unwrap(), Some((23, 23).into()));
+
+        db.make_index(CaseSensitivity::Sensitive).await.unwrap();
+
+        assert_eq!(db.search("foo").await.unwrap(), vec![1, 2]);
+    }
+}
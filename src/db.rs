@@ -1,16 +1,33 @@
 use super::{
     error::Error,
+    key::{Key, KeyCodec},
+    metrics::{Callback, ErrorObserver, MetricsSink, Timer},
+    raw::{count_ids_and_values, read_id_bounds, read_raw_bytes},
     table::{Mode, Table, Writeable},
-    value::{Set64, Value},
+    value::{Range32, Set64, Timestamps, Value},
 };
 use rocksdb::{
-    BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, DBIterator, DataBlockIndexType,
-    IteratorMode, MergeOperands, Options, SliceTransform, WriteBatch, DB,
+    backup::{BackupEngine, BackupEngineOptions, RestoreOptions},
+    compaction_filter::Decision,
+    BlockBasedIndexType, BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor,
+    DBCompressionType, DBIterator, DBPinnableSlice, DBRecoveryMode, DataBlockIndexType, Direction,
+    Env, IteratorMode, MergeOperands, Options, SliceTransform, SstFileWriter, WriteBatch, DB,
 };
-use std::collections::{HashMap, HashSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// The key in the `meta` column family under which the value type's tag is stored.
+const VALUE_TYPE_KEY: &[u8] = b"value_type";
+
+/// The key in the `meta` column family under which the incremental indexing watermark is stored.
+const INDEX_WATERMARK_KEY: &[u8] = b"index_watermark";
+
+/// The default number of index merges per write batch when building the index.
+const INDEX_BATCH_SIZE: usize = 10_000;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum CaseSensitivity {
@@ -18,15 +35,59 @@ pub enum CaseSensitivity {
     Insensitive,
 }
 
-#[derive(Clone)]
-pub struct Hkvdb<M, V> {
+/// How writes are combined with existing values.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum WriteMode {
+    /// Merge new values into existing values using the value type's `Add` implementation.
+    #[default]
+    Merge,
+    /// Replace existing values, ignoring the value type's `Add` implementation.
+    Overwrite,
+}
+
+/// The SST files in one level of the LSM tree for a column family.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LevelStat {
+    pub level: usize,
+    pub num_files: u64,
+    pub size_bytes: u64,
+}
+
+/// A historical key-value store.
+///
+/// Values are stored under ids of type `K`, which defaults to `u64`. Indexing and search are
+/// only supported for `u64` ids.
+pub struct Hkvdb<M, V, K = u64> {
     db: Arc<DB>,
     options: Options,
+    normalize_keys: CaseSensitivity,
+    auto_index: Option<CaseSensitivity>,
+    write_mode: WriteMode,
+    metrics: Option<Arc<dyn MetricsSink>>,
     _mode: PhantomData<M>,
     _merge: PhantomData<V>,
+    _key: PhantomData<K>,
 }
 
-impl<M, V> Table for Hkvdb<M, V> {
+// Implemented manually because the derived instance would require the type parameters to be
+// `Clone`.
+impl<M, V, K> Clone for Hkvdb<M, V, K> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            options: self.options.clone(),
+            normalize_keys: self.normalize_keys,
+            auto_index: self.auto_index,
+            write_mode: self.write_mode,
+            metrics: self.metrics.clone(),
+            _mode: PhantomData,
+            _merge: PhantomData,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<M, V, K: Key> Table for Hkvdb<M, V, K> {
     type Counts = (u64, u64);
 
     fn underlying(&self) -> &DB {
@@ -34,40 +95,234 @@ impl<M, V> Table for Hkvdb<M, V> {
     }
 
     fn get_counts(&self) -> Result<Self::Counts, Error> {
-        let mut ids = HashSet::new();
-        let mut value_count = 0;
+        count_ids_and_values::<K>(&self.db, self.by_id_cf())
+    }
+}
 
-        let iter = self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start);
+impl<M, V, K: Key> Hkvdb<M, V, K> {
+    pub fn statistics(&self) -> Option<String> {
+        self.options.get_statistics()
+    }
 
-        for result in iter {
-            let (key, _) = result?;
-            let id = u64::from_be_bytes(
-                key[0..8]
-                    .try_into()
-                    .map_err(|_| Error::InvalidKey(key.to_vec()))?,
-            );
+    /// The current values of all RocksDB ticker statistics, by name.
+    ///
+    /// Returns `None` if statistics aren't enabled. Histogram statistics are not included.
+    pub fn statistics_parsed(&self) -> Option<HashMap<String, f64>> {
+        let statistics = self.statistics()?;
+
+        Some(
+            statistics
+                .lines()
+                .filter_map(parse_ticker_line)
+                .map(|(name, count)| (name.to_string(), count as f64))
+                .collect(),
+        )
+    }
+
+    /// The change in each ticker statistic since the baseline (from `statistics_parsed`).
+    ///
+    /// RocksDB's statistics are cumulative from when the database was opened and can't be reset,
+    /// so this can be used to measure a single phase of work. Tickers that are missing from the
+    /// baseline are treated as zero. Returns `None` if statistics aren't enabled.
+    pub fn statistics_delta_since(
+        &self,
+        baseline: &HashMap<String, f64>,
+    ) -> Option<HashMap<String, f64>> {
+        let mut current = self.statistics_parsed()?;
 
-            ids.insert(id);
-            value_count += 1;
+        for (name, value) in current.iter_mut() {
+            *value -= baseline.get(name).copied().unwrap_or_default();
         }
 
-        Ok((ids.len() as u64, value_count))
+        Some(current)
     }
-}
 
-impl<M, V> Hkvdb<M, V> {
-    pub fn statistics(&self) -> Option<String> {
-        self.options.get_statistics()
+    /// The proportion of block cache lookups that were hits.
+    ///
+    /// Returns `None` if statistics aren't enabled or if the block cache hasn't been used yet.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let statistics = self.statistics()?;
+        let hits = parse_ticker(&statistics, "rocksdb.block.cache.hit")?;
+        let misses = parse_ticker(&statistics, "rocksdb.block.cache.miss")?;
+        let total = hits + misses;
+
+        if total == 0 {
+            None
+        } else {
+            Some(hits as f64 / total as f64)
+        }
+    }
+
+    /// The total size of the SST files for all column families.
+    ///
+    /// This doesn't include the write-ahead log or data that is still in memory.
+    pub fn size_on_disk(&self) -> Result<u64, Error> {
+        let mut size = 0;
+
+        for cf in std::iter::once(self.by_id_cf())
+            .chain(self.index_cf().ok())
+            .chain(self.db.cf_handle("index_last_seen"))
+        {
+            size += self
+                .db
+                .property_int_value_cf(cf, "rocksdb.total-sst-files-size")?
+                .unwrap_or(0);
+        }
+
+        Ok(size)
+    }
+
+    /// The number and total size of the SST files in each level for the `by_id` column family.
+    ///
+    /// Only levels that contain files are included, in order.
+    pub fn level_stats(&self) -> Result<Vec<LevelStat>, Error> {
+        let mut levels = BTreeMap::new();
+
+        for file in self.db.live_files()? {
+            if file.column_family_name == "by_id" {
+                let (num_files, size_bytes) = levels.entry(file.level).or_insert((0, 0));
+                *num_files += 1;
+                *size_bytes += file.size as u64;
+            }
+        }
+
+        Ok(levels
+            .into_iter()
+            .map(|(level, (num_files, size_bytes))| LevelStat {
+                level: level as usize,
+                num_files,
+                size_bytes,
+            })
+            .collect())
+    }
+
+    /// Estimate the number of keys with ids from `start` (inclusive) to `end` (exclusive).
+    ///
+    /// This adds up the entry counts of the SST files for the `by_id` column family that overlap
+    /// the range, so it doesn't require a scan, but it may count keys outside the range (or
+    /// multiple versions of the same key), and it doesn't include writes that haven't been
+    /// flushed.
+    pub fn estimate_keys_in_range(&self, start: K, end: K) -> Result<u64, Error> {
+        let start = KeyCodec::prefix(&start);
+        let end = KeyCodec::prefix(&end);
+        let mut count = 0;
+
+        for file in self.db.live_files()? {
+            if file.column_family_name == "by_id" {
+                let overlaps = matches!(&file.start_key, Some(file_start) if *file_start < end)
+                    && matches!(&file.end_key, Some(file_end) if *file_end >= start);
+
+                if overlaps {
+                    count += file.num_entries.saturating_sub(file.num_deletions);
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Create a new incremental backup of the database in the given directory.
+    pub fn backup<P: AsRef<Path>>(&self, backup_dir: P) -> Result<(), Error> {
+        let mut engine = BackupEngine::open(&BackupEngineOptions::new(backup_dir)?, &Env::new()?)?;
+        engine.create_new_backup(&self.db)?;
+        Ok(())
+    }
+
+    /// Read the stored bytes for all keys for an id without decoding the values.
+    pub fn get_raw_bytes(&self, id: K) -> Result<HashMap<Vec<u8>, Vec<u8>>, Error> {
+        read_raw_bytes(&self.db, self.by_id_cf(), &id)
+    }
+
+    /// The smallest and largest ids in the database, or `None` if it's empty.
+    ///
+    /// This only requires seeking to the first and last keys, not a full scan.
+    pub fn id_bounds(&self) -> Result<Option<(K, K)>, Error> {
+        read_id_bounds(&self.db, self.by_id_cf())
+    }
+
+    /// Read all keys for the ids so that their blocks are loaded into the block cache.
+    ///
+    /// This can reduce latency for later reads for these ids. The data read is discarded.
+    pub fn prefetch_ids(&self, ids: &[K]) -> Result<(), Error> {
+        for id in ids {
+            let prefix = KeyCodec::prefix(id);
+
+            for result in self.db.prefix_iterator_cf(self.by_id_cf(), &prefix) {
+                let (key, _) = result?;
+
+                if !key.starts_with(&prefix) {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compact the range of keys for a single id.
+    pub fn compact_id(&self, id: K) -> Result<(), Error> {
+        let start = KeyCodec::prefix(&id);
+        let end = prefix_successor(&start);
+
+        self.db
+            .compact_range_cf(self.by_id_cf(), Some(&start), end.as_deref());
+
+        Ok(())
+    }
+
+    /// Flush all data to disk and close this handle, reporting any errors.
+    ///
+    /// The database is only actually closed when the last handle is dropped, so this should only
+    /// be called on the last handle (clones share the same underlying database).
+    pub fn close(self) -> Result<(), Error> {
+        // Every column family has to be opened, so this includes optional ones like the index.
+        for name in DB::list_cf(&self.options, self.db.path())? {
+            self.db.flush_cf(self.cf_handle(&name)?)?;
+        }
+
+        Ok(self.db.flush_wal(true)?)
+    }
+
+    /// The underlying RocksDB database.
+    ///
+    /// This is an escape hatch for advanced uses that aren't otherwise supported. Writing to the
+    /// database directly can break the invariants that this library relies on.
+    pub fn raw_db(&self) -> &DB {
+        &self.db
+    }
+
+    /// The handle for the `by_id` column family, for use with [`Hkvdb::raw_db`].
+    pub fn by_id_cf_handle(&self) -> Result<&ColumnFamily, Error> {
+        self.cf_handle("by_id")
+    }
+
+    /// The handle for the `index` column family, for use with [`Hkvdb::raw_db`].
+    pub fn index_cf_handle(&self) -> Result<&ColumnFamily, Error> {
+        self.index_cf()
+    }
+
+    fn cf_handle(&self, name: &str) -> Result<&ColumnFamily, Error> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| Error::MissingColumnFamily(name.to_string()))
+    }
+
+    fn timer(&self, callback: Callback) -> Option<Timer<'_>> {
+        self.metrics
+            .as_ref()
+            .map(|metrics| Timer::start(metrics.as_ref(), callback))
     }
 
     fn by_id_cf(&self) -> &ColumnFamily {
         self.db.cf_handle("by_id").unwrap()
     }
 
-    fn index_cf(&self) -> &ColumnFamily {
-        self.db.cf_handle("index").unwrap()
+    fn index_cf(&self) -> Result<&ColumnFamily, Error> {
+        self.db.cf_handle("index").ok_or(Error::IndexDisabled)
     }
+}
 
+impl<M, V> Hkvdb<M, V> {
     pub fn search_raw(
         &self,
         data: &[u8],
@@ -75,7 +330,7 @@ impl<M, V> Hkvdb<M, V> {
     ) -> Result<Vec<u64>, Error> {
         let key = make_index_key(data, case_sensitivity)?;
 
-        match self.db.get_pinned_cf(self.index_cf(), key)? {
+        match self.db.get_pinned_cf(self.index_cf()?, key)? {
             Some(bytes) => Ok(Set64::try_from(bytes.as_ref())?.into_inner()),
             None => Ok(vec![]),
         }
@@ -85,405 +340,4304 @@ impl<M, V> Hkvdb<M, V> {
         self.search_raw(data.as_bytes(), CaseSensitivity::Sensitive)
     }
 
+    /// Search the case-sensitive index, also returning the most recent timestamp for the term.
+    ///
+    /// This requires [`HkvdbBuilder::index_last_seen`]. The timestamp is `None` if none has been
+    /// recorded for the term.
+    pub fn search_with_last_seen(&self, data: &str) -> Result<(Vec<u64>, Option<u32>), Error> {
+        let last_seen_cf = self.cf_handle("index_last_seen")?;
+        let ids = self.search(data)?;
+        let key = make_index_key(data.as_bytes(), CaseSensitivity::Sensitive)?;
+
+        let last_seen = match self.db.get_pinned_cf(last_seen_cf, key)? {
+            Some(bytes) => Some(u32::from_be_bytes(
+                bytes
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(&bytes))?,
+            )),
+            None => None,
+        };
+
+        Ok((ids, last_seen))
+    }
+
     pub fn search_ci(&self, data: &str) -> Result<Vec<u64>, Error> {
-        self.search_raw(data.to_lowercase().as_bytes(), CaseSensitivity::Insensitive)
+        self.search_ci_prenormalized(&data.to_lowercase())
     }
-}
 
-impl<M: Mode + 'static, V: Value + 'static> Hkvdb<M, V> {
-    pub fn new<P: AsRef<Path>>(path: P, enable_statistics: bool) -> Result<Self, Error> {
-        let mut options = Options::default();
-        options.create_missing_column_families(true);
-        options.create_if_missing(true);
+    /// Search the case-sensitive index, falling back to the case-insensitive index if there are
+    /// no exact matches.
+    ///
+    /// Both indexes share a column family, so if only a case-insensitive index was built, an
+    /// exact search for a lowercase term will already return the case-insensitive matches, and
+    /// if only a case-sensitive index was built, the fallback will only find data that was
+    /// lowercase to begin with. The results are therefore only unambiguous when the index was
+    /// built consistently.
+    pub fn search_either(&self, data: &str) -> Result<Vec<u64>, Error> {
+        let ids = self.search(data)?;
 
-        if enable_statistics {
-            options.enable_statistics();
+        if ids.is_empty() {
+            self.search_ci(data)
+        } else {
+            Ok(ids)
         }
+    }
 
-        let mut by_id_cf_block_options = BlockBasedOptions::default();
-        by_id_cf_block_options.set_data_block_index_type(DataBlockIndexType::BinaryAndHash);
-        by_id_cf_block_options.set_block_cache(&rocksdb::Cache::new_lru_cache(32768 * 2)?);
+    /// Search the case-insensitive index with a query that has already been lowercased.
+    pub fn search_ci_prenormalized(&self, lowercased: &str) -> Result<Vec<u64>, Error> {
+        debug_assert_eq!(lowercased, lowercased.to_lowercase());
 
-        let mut by_id_cf_options = Options::default();
-        by_id_cf_options.set_block_based_table_factory(&by_id_cf_block_options);
-        by_id_cf_options.set_merge_operator_associative("merge_by_id", Self::merge_by_id);
-        by_id_cf_options.set_prefix_extractor(SliceTransform::create_fixed_prefix(8));
+        // Index keys are the same in both cases once the input has been lowercased.
+        self.search_raw(lowercased.as_bytes(), CaseSensitivity::Sensitive)
+    }
 
-        let mut index_cf_block_options = BlockBasedOptions::default();
-        index_cf_block_options.set_data_block_index_type(DataBlockIndexType::BinaryAndHash);
+    /// Search the case-sensitive index, decoding ids lazily from the stored bytes.
+    pub fn search_iter(&self, data: &str) -> Result<IdIterator<'_>, Error> {
+        let key = make_index_key(data.as_bytes(), CaseSensitivity::Sensitive)?;
 
-        let mut index_cf_options = Options::default();
-        index_cf_options.set_block_based_table_factory(&index_cf_block_options);
-        index_cf_options.set_merge_operator_associative("merge_index", Self::merge_index);
+        IdIterator::new(self.db.get_pinned_cf(self.index_cf()?, key)?)
+    }
 
-        let by_id_cf = ColumnFamilyDescriptor::new("by_id", by_id_cf_options);
-        let index_cf = ColumnFamilyDescriptor::new("index", index_cf_options);
+    /// Search the case-sensitive index for ids matching any of the terms.
+    ///
+    /// The ids are yielded in sorted order without duplicates, and are decoded lazily, so memory
+    /// usage is proportional to the number of terms, not the number of ids.
+    pub fn search_any_iter(&self, terms: &[&str]) -> Result<UnionIterator<'_>, Error> {
+        let iters = terms
+            .iter()
+            .map(|term| self.search_iter(term))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let db = DB::open_cf_descriptors(&options, path, vec![by_id_cf, index_cf])?;
+        Ok(UnionIterator::new(iters))
+    }
 
-        Ok(Self {
-            db: Arc::new(db),
-            options,
-            _mode: PhantomData,
-            _merge: PhantomData,
-        })
+    /// Search the case-sensitive index for several terms with a single lookup.
+    ///
+    /// Terms that aren't in the index are mapped to an empty list.
+    pub fn search_many(&self, terms: &[&str]) -> Result<HashMap<String, Vec<u64>>, Error> {
+        let keys = terms
+            .iter()
+            .map(|term| make_index_key(term.as_bytes(), CaseSensitivity::Sensitive))
+            .collect::<Result<Vec<_>, _>>()?;
+        let index_cf = self.index_cf()?;
+
+        terms
+            .iter()
+            .zip(self.db.multi_get_cf(keys.iter().map(|key| (index_cf, key))))
+            .map(|(term, result)| -> Result<_, Error> {
+                let ids = match result? {
+                    Some(bytes) => Set64::try_from(bytes.as_slice())?.into_inner(),
+                    None => vec![],
+                };
+
+                Ok((term.to_string(), ids))
+            })
+            .collect()
     }
-}
 
-impl<M, V: Value> Hkvdb<M, V> {
-    pub fn get_raw(&self, id: u64) -> Result<HashMap<Vec<u8>, V>, Error> {
-        let prefix = make_prefix(id);
-        let mut results = HashMap::new();
-        let iter = self.db.prefix_iterator_cf(self.by_id_cf(), prefix);
+    /// Search the case-sensitive index, returning at most `limit` ids starting at `offset`.
+    ///
+    /// Only the ids in the requested page are decoded.
+    pub fn search_paged(&self, data: &str, offset: usize, limit: usize) -> Result<Vec<u64>, Error> {
+        Ok(self.search_iter(data)?.skip(offset).take(limit).collect())
+    }
 
-        for result in iter {
-            let (key, value_bytes) = result?;
-            let next_id = u64::from_be_bytes(
-                key[0..8]
-                    .try_into()
-                    .map_err(|_| Error::InvalidKey(key.to_vec()))?,
-            );
+    pub fn search_with_count(&self, data: &str) -> Result<(usize, Vec<u64>), Error> {
+        let ids = self.search(data)?;
+        Ok((ids.len(), ids))
+    }
 
-            if next_id == id {
-                let value = V::prepare(&value_bytes)?;
-                results.insert(key[8..].to_vec(), value);
-            } else {
-                break;
-            }
+    /// Count the ids for a term in the case-sensitive index without decoding them.
+    ///
+    /// The count is computed from the length of the stored value, so this is constant-time
+    /// after the lookup.
+    pub fn search_count(&self, data: &str) -> Result<usize, Error> {
+        let key = make_index_key(data.as_bytes(), CaseSensitivity::Sensitive)?;
+
+        match self.db.get_pinned_cf(self.index_cf()?, key)? {
+            Some(bytes) => count_ids(&bytes),
+            None => Ok(0),
         }
+    }
 
-        Ok(results)
+    /// Whether an id is in the case-sensitive index for a term.
+    ///
+    /// The stored ids are sorted, so this is a binary search over the stored bytes, without
+    /// decoding the other ids.
+    pub fn search_contains(&self, data: &str, id: u64) -> Result<bool, Error> {
+        let key = make_index_key(data.as_bytes(), CaseSensitivity::Sensitive)?;
+
+        match self.db.get_pinned_cf(self.index_cf()?, key)? {
+            Some(bytes) => contains_id(&bytes, id),
+            None => Ok(false),
+        }
     }
 
-    pub fn get(&self, id: u64) -> Result<HashMap<String, V>, Error> {
-        let as_bytes = self.get_raw(id)?;
-        let mut result = HashMap::with_capacity(as_bytes.len());
+    /// Count the ids shared by two terms in the case-sensitive index.
+    ///
+    /// Both id lists are sorted, so they're compared in a single pass while being decoded lazily
+    /// from the stored bytes, without allocating either list.
+    pub fn search_intersection_count(&self, a: &str, b: &str) -> Result<usize, Error> {
+        let mut a_ids = self.search_iter(a)?;
+        let mut b_ids = self.search_iter(b)?;
+        let mut a_next = a_ids.next();
+        let mut b_next = b_ids.next();
+        let mut count = 0;
 
-        for (k, v) in as_bytes {
-            result.insert(String::from_utf8(k).map_err(|error| error.utf8_error())?, v);
+        while let (Some(a_id), Some(b_id)) = (a_next, b_next) {
+            match a_id.cmp(&b_id) {
+                Ordering::Less => a_next = a_ids.next(),
+                Ordering::Greater => b_next = b_ids.next(),
+                Ordering::Equal => {
+                    count += 1;
+                    a_next = a_ids.next();
+                    b_next = b_ids.next();
+                }
+            }
         }
 
-        Ok(result)
+        Ok(count)
     }
 
-    pub fn iter_raw(&self) -> RawIterator<V> {
-        RawIterator {
-            underlying: self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start),
-            _merge: PhantomData,
+    /// Estimate the number of index merges that `make_index` will perform.
+    ///
+    /// Each key in `by_id` produces one merge. This uses RocksDB's key count estimate, which
+    /// is cheap but may be inaccurate (especially before compaction), and falls back to
+    /// counting keys if the estimate isn't available.
+    pub fn estimate_index_work(&self) -> Result<u64, Error> {
+        match self
+            .db
+            .property_int_value_cf(self.by_id_cf(), "rocksdb.estimate-num-keys")?
+        {
+            Some(estimate) => Ok(estimate),
+            None => Ok(self.get_counts()?.1),
         }
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = Result<(u64, String, V), Error>> + '_ {
-        self.iter_raw().map(|result| {
-            result.and_then(|(id, bytes, value)| {
-                Ok((
-                    id,
-                    String::from_utf8(bytes).map_err(|error| error.utf8_error())?,
-                    value,
-                ))
-            })
+    /// Iterate over all index terms along with the number of ids for each, without decoding
+    /// the ids.
+    pub fn index_term_counts(&self) -> impl Iterator<Item = Result<(String, usize), Error>> + '_ {
+        self.index_entries().map(|result| {
+            let (key, value_bytes) = result?;
+            let term = String::from_utf8(key.to_vec()).map_err(Error::invalid_utf8_key)?;
+
+            Ok((term, count_ids(&value_bytes)?))
         })
     }
 
-    fn merge_by_id(
-        _key: &[u8],
-        existing_value: Option<&[u8]>,
-        operands: &MergeOperands,
-    ) -> Option<Vec<u8>> {
-        V::merge(existing_value, operands.iter()).unwrap_or_else(|(error, fallback_value)| {
-            // The RocksDb library doesn't let us fail in a merge, so we just log the
-            // error and use the last value before the error. This should never happen.
-            log::error!("Error during aggregation in merge: {:?}", error);
+    /// Estimate the number of distinct terms in the index.
+    ///
+    /// This uses RocksDB's key count estimate, which is cheap but may be inaccurate (especially
+    /// before compaction, since each index merge may be counted separately), and falls back to
+    /// counting terms if the estimate isn't available.
+    pub fn index_term_count(&self) -> Result<u64, Error> {
+        match self
+            .db
+            .property_int_value_cf(self.index_cf()?, "rocksdb.estimate-num-keys")?
+        {
+            Some(estimate) => Ok(estimate),
+            None => {
+                let mut count = 0;
 
-            fallback_value
-        })
+                for result in self.db.iterator_cf(self.index_cf()?, IteratorMode::Start) {
+                    result?;
+                    count += 1;
+                }
+
+                Ok(count)
+            }
+        }
     }
 
-    fn merge_index(
-        _key: &[u8],
-        existing_value: Option<&[u8]>,
-        operands: &MergeOperands,
-    ) -> Option<Vec<u8>> {
-        Set64::merge(existing_value, operands.iter()).unwrap_or_else(|(error, fallback_value)| {
-            // The RocksDb library doesn't let us fail in a merge, so we just log the
-            // error and use the last value before the error. This should never happen.
-            log::error!("Error during aggregation in index merge: {:?}", error);
+    /// Find index terms that contain ids with no matching key in the data.
+    ///
+    /// This assumes that the index was built with the given case sensitivity. Terms that can't
+    /// be decoded as UTF-8 are reported as errors.
+    pub fn verify_index(&self, case_sensitivity: CaseSensitivity) -> Result<Vec<String>, Error> {
+        let mut stale_terms = vec![];
 
-            fallback_value
-        })
+        for result in self.db.iterator_cf(self.index_cf()?, IteratorMode::Start) {
+            let (term, value_bytes) = result?;
+            let ids = Set64::try_from(value_bytes.as_ref())?;
+
+            for id in ids {
+                if !self.has_index_term(id, &term, case_sensitivity)? {
+                    stale_terms
+                        .push(String::from_utf8(term.to_vec()).map_err(Error::invalid_utf8_key)?);
+                    break;
+                }
+            }
+        }
+
+        Ok(stale_terms)
     }
-}
 
-pub struct RawIterator<'a, V> {
-    underlying: DBIterator<'a>,
-    _merge: PhantomData<V>,
-}
+    /// Whether any of the keys for an id produce the given index term.
+    fn has_index_term(
+        &self,
+        id: u64,
+        term: &[u8],
+        case_sensitivity: CaseSensitivity,
+    ) -> Result<bool, Error> {
+        match case_sensitivity {
+            CaseSensitivity::Sensitive => Ok(self
+                .db
+                .get_pinned_cf(self.by_id_cf(), KeyCodec::encode(&id, term))?
+                .is_some()),
+            CaseSensitivity::Insensitive => {
+                let prefix = KeyCodec::prefix(&id);
 
-impl<'a, V: Value> RawIterator<'a, V> {
-    fn parse(key: &[u8], value_bytes: &[u8]) -> <Self as Iterator>::Item {
-        let id = u64::from_be_bytes(
-            key[0..8]
-                .try_into()
-                .map_err(|_| Error::InvalidKey(key.to_vec()))?,
-        );
+                for result in self.db.prefix_iterator_cf(self.by_id_cf(), &prefix) {
+                    let (key, _) = result?;
+                    let (id_bytes, data) = KeyCodec::<u64>::split(&key)?;
 
-        let value = V::prepare(value_bytes)?;
+                    if id_bytes != prefix {
+                        break;
+                    } else if make_index_key(data, case_sensitivity)? == term {
+                        return Ok(true);
+                    }
+                }
 
-        Ok((id, key[8..].to_vec(), value))
+                Ok(false)
+            }
+        }
     }
-}
 
-impl<'a, V: Value> Iterator for RawIterator<'a, V> {
-    type Item = Result<(u64, Vec<u8>, V), Error>;
+    /// The next id to index with [`Hkvdb::make_index_incremental`], if it has been run.
+    pub fn index_watermark(&self) -> Result<Option<u64>, Error> {
+        let meta_cf = self.cf_handle("meta")?;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.underlying.next().map(|result| {
-            result
-                .map_err(Error::from)
-                .and_then(|(key, value_bytes)| Self::parse(&key, &value_bytes))
+        self.db
+            .get_pinned_cf(meta_cf, INDEX_WATERMARK_KEY)?
+            .map(|bytes| u64::from_bytes(&bytes))
+            .transpose()
+    }
+
+    /// Iterate over all index entries, for example to back up the index separately from the data.
+    pub fn iter_index(&self) -> impl Iterator<Item = Result<(Vec<u8>, Set64), Error>> + '_ {
+        self.index_entries().map(|result| {
+            let (key, value_bytes) = result?;
+
+            Ok((key.into_vec(), Set64::try_from(value_bytes.as_ref())?))
         })
     }
-}
 
-impl<V> Hkvdb<Writeable, V> {
-    pub fn make_index(&self, case_sensitivity: CaseSensitivity) -> Result<(), Error> {
-        let iter = self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start);
+    /// Iterate over every term and id pair in the index, in index key order.
+    pub fn iter_term_ids(&self) -> impl Iterator<Item = Result<(String, u64), Error>> + '_ {
+        self.iter_index().flat_map(|result| {
+            let term_ids = result.and_then(|(key, ids)| {
+                Ok((
+                    String::from_utf8(key).map_err(Error::invalid_utf8_key)?,
+                    ids,
+                ))
+            });
 
-        for result in iter {
-            let (id_data_key, _) = result?;
-            let id = u64::from_be_bytes(
-                id_data_key[0..8]
-                    .try_into()
-                    .map_err(|_| Error::InvalidKey(id_data_key.to_vec()))?,
-            );
+            match term_ids {
+                Ok((term, ids)) => ids.into_iter().map(|id| Ok((term.clone(), id))).collect(),
+                Err(error) => vec![Err(error)],
+            }
+        })
+    }
 
-            let index_key = make_index_key(&id_data_key[8..], case_sensitivity)?;
-            let id_bytes: Vec<u8> = Set64::singleton(id).into();
+    /// Iterate over the raw index entries, yielding a single error if the index is disabled.
+    fn index_entries(&self) -> impl Iterator<Item = Result<Row, Error>> + '_ {
+        let (iter, error) = match self.index_cf() {
+            Ok(cf) => (Some(self.db.iterator_cf(cf, IteratorMode::Start)), None),
+            Err(error) => (None, Some(error)),
+        };
 
-            self.db.merge_cf(self.index_cf(), &index_key, &id_bytes)?;
+        error.map(Err).into_iter().chain(
+            iter.into_iter()
+                .flatten()
+                .map(|result| result.map_err(Error::from)),
+        )
+    }
+
+    /// Count the index terms by the number of ids they map to.
+    pub fn index_size_histogram(&self) -> Result<BTreeMap<usize, u64>, Error> {
+        let mut histogram = BTreeMap::new();
+
+        for result in self.db.iterator_cf(self.index_cf()?, IteratorMode::Start) {
+            let (_, value_bytes) = result?;
+            *histogram.entry(count_ids(&value_bytes)?).or_default() += 1;
         }
 
-        Ok(())
+        Ok(histogram)
     }
 }
 
-impl<V: Value> Hkvdb<Writeable, V> {
-    pub fn put_raw<IV: Into<V>>(&self, id: u64, data: &[u8], value: IV) -> Result<(), Error> {
-        let key = make_key(id, data);
-        self.db
-            .merge_cf(self.by_id_cf(), key, value.into().into())?;
-        Ok(())
+/// Count the ids in an encoded `Set64` without decoding them.
+fn count_ids(bytes: &[u8]) -> Result<usize, Error> {
+    let chunks = bytes.chunks_exact(8);
+
+    if chunks.remainder().is_empty() {
+        Ok(chunks.len())
+    } else {
+        Err(Error::invalid_value(bytes))
     }
+}
 
-    pub fn put_raw_batch<'a, IV: Into<V>, I: IntoIterator<Item = (u64, &'a [u8], IV)>>(
-        &'a self,
-        batch: I,
-    ) -> Result<(), Error> {
-        let cf = self.by_id_cf();
-        let mut wb = WriteBatch::default();
+/// Check whether an encoded `Set64` contains an id without decoding the others.
+fn contains_id(bytes: &[u8], id: u64) -> Result<bool, Error> {
+    if !bytes.chunks_exact(8).remainder().is_empty() {
+        return Err(Error::invalid_value(bytes));
+    }
 
-        for (id, data, value) in batch {
-            let key = make_key(id, data);
-            wb.merge_cf(cf, key, value.into().into());
+    let mut low = 0;
+    let mut high = bytes.len() / 8;
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let offset = middle * 8;
+        let current = u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        match current.cmp(&id) {
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle,
+            Ordering::Equal => return Ok(true),
         }
+    }
 
-        Ok(self.db.write(wb)?)
+    Ok(false)
+}
+
+impl<M: Mode + 'static, V: Value + 'static, K: Key + 'static> Hkvdb<M, V, K> {
+    pub fn new<P: AsRef<Path>>(path: P, enable_statistics: bool) -> Result<Self, Error> {
+        Self::builder()
+            .enable_statistics(enable_statistics)
+            .open(path)
     }
 
-    pub fn put<IV: Into<V>>(&self, id: u64, data: &str, value: IV) -> Result<(), Error> {
-        self.put_raw(id, data.as_bytes(), value)
+    /// Open a database, failing if it doesn't already exist.
+    pub fn open_existing<P: AsRef<Path>>(path: P, enable_statistics: bool) -> Result<Self, Error> {
+        Self::builder()
+            .enable_statistics(enable_statistics)
+            .create_if_missing(false)
+            .open(path)
     }
 
-    pub fn put_batch<S: AsRef<str>, IV: Into<V>, I: IntoIterator<Item = (u64, S, IV)>>(
-        &self,
-        batch: I,
-    ) -> Result<(), Error> {
-        let cf = self.by_id_cf();
-        let mut wb = WriteBatch::default();
+    /// Open a database that optionally lowercases the data part of keys on writes and lookups.
+    ///
+    /// With `CaseSensitivity::Insensitive`, observations for case variants of the same data are
+    /// merged into a single key. The original case is not stored and can't be recovered.
+    pub fn new_with_key_case<P: AsRef<Path>>(
+        path: P,
+        enable_statistics: bool,
+        normalize_keys: CaseSensitivity,
+    ) -> Result<Self, Error> {
+        Self::builder()
+            .enable_statistics(enable_statistics)
+            .normalize_keys(normalize_keys)
+            .open(path)
+    }
 
-        for (id, data, value) in batch {
-            let key = make_key(id, data.as_ref().as_bytes());
-            wb.merge_cf(cf, key, value.into().into());
-        }
+    /// Open a database in which entries expire after the given number of seconds.
+    ///
+    /// See [`HkvdbBuilder::ttl`] for details about how expiration works.
+    pub fn new_with_ttl<P: AsRef<Path>>(
+        path: P,
+        ttl_secs: u64,
+        enable_statistics: bool,
+    ) -> Result<Self, Error> {
+        Self::builder()
+            .enable_statistics(enable_statistics)
+            .ttl(Duration::from_secs(ttl_secs))
+            .open(path)
+    }
 
-        Ok(self.db.write(wb)?)
+    pub fn builder() -> HkvdbBuilder<M, V, K> {
+        HkvdbBuilder::new()
     }
 }
 
-fn make_prefix(id: u64) -> Vec<u8> {
-    let mut key = Vec::with_capacity(8);
-    key.extend_from_slice(&id.to_be_bytes());
-    key
+/// Configuration for opening a [`Hkvdb`].
+pub struct HkvdbBuilder<M, V, K = u64> {
+    block_cache_bytes: usize,
+    enable_statistics: bool,
+    create_if_missing: bool,
+    wal_recovery_mode: Option<DBRecoveryMode>,
+    max_open_files: Option<i32>,
+    write_buffer_size: Option<usize>,
+    normalize_keys: CaseSensitivity,
+    ttl: Option<Duration>,
+    auto_index: Option<CaseSensitivity>,
+    write_mode: WriteMode,
+    compression: Option<DBCompressionType>,
+    compression_level: Option<i32>,
+    bloom_bits_per_key: Option<f64>,
+    two_level_index: bool,
+    with_index: bool,
+    index_last_seen: bool,
+    expire_before: Option<u32>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    error_observer: Option<Arc<dyn ErrorObserver>>,
+    _mode: PhantomData<M>,
+    _merge: PhantomData<V>,
+    _key: PhantomData<K>,
 }
 
-fn make_key(id: u64, value: &[u8]) -> Vec<u8> {
-    let mut key = Vec::with_capacity(value.len() + 8);
-    key.extend_from_slice(&id.to_be_bytes());
-    key.extend_from_slice(value);
-    key
+// Implemented manually because the derived instance would require the type parameters to be
+// `Default`.
+impl<M, V, K> Default for HkvdbBuilder<M, V, K> {
+    fn default() -> Self {
+        Self {
+            block_cache_bytes: 32768 * 2,
+            enable_statistics: false,
+            create_if_missing: true,
+            wal_recovery_mode: None,
+            max_open_files: None,
+            write_buffer_size: None,
+            normalize_keys: CaseSensitivity::Sensitive,
+            ttl: None,
+            auto_index: None,
+            write_mode: WriteMode::Merge,
+            compression: None,
+            compression_level: None,
+            bloom_bits_per_key: None,
+            two_level_index: false,
+            with_index: true,
+            index_last_seen: false,
+            expire_before: None,
+            metrics: None,
+            error_observer: None,
+            _mode: PhantomData,
+            _merge: PhantomData,
+            _key: PhantomData,
+        }
+    }
 }
 
-pub fn make_index_key(data: &[u8], case_sensitivity: CaseSensitivity) -> Result<Vec<u8>, Error> {
-    let mut key = Vec::with_capacity(data.len());
+impl<M, V, K> HkvdbBuilder<M, V, K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    if case_sensitivity == CaseSensitivity::Insensitive {
-        let as_string = std::str::from_utf8(data)?;
-        let lowercase = as_string.to_lowercase();
+    /// The capacity of the block cache for the `by_id` column family.
+    pub fn block_cache_bytes(mut self, block_cache_bytes: usize) -> Self {
+        self.block_cache_bytes = block_cache_bytes;
+        self
+    }
 
-        key.extend(lowercase.as_bytes());
+    pub fn enable_statistics(mut self, enable_statistics: bool) -> Self {
+        self.enable_statistics = enable_statistics;
+        self
+    }
+
+    /// Whether to create a new database if there isn't one at the path (the default).
+    ///
+    /// If this is false, opening a path without a database fails with `Error::Db`.
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.create_if_missing = create_if_missing;
+        self
+    }
+
+    /// How to handle corruption in the write-ahead log when opening the database.
+    ///
+    /// If this isn't set, RocksDB's default is used. For example `DBRecoveryMode::PointInTime`
+    /// stops replaying the log at the first corrupted record, which can allow a database to be
+    /// opened after an unclean shutdown left a bad log, at the cost of losing the later writes.
+    pub fn wal_recovery_mode(mut self, wal_recovery_mode: DBRecoveryMode) -> Self {
+        self.wal_recovery_mode = Some(wal_recovery_mode);
+        self
+    }
+
+    /// The maximum number of files RocksDB keeps open (unlimited by default).
+    ///
+    /// When many databases are open in the same process, this can be used to stay under the
+    /// file descriptor limit, at the cost of reopening table files more often.
+    pub fn max_open_files(mut self, max_open_files: i32) -> Self {
+        self.max_open_files = Some(max_open_files);
+        self
+    }
+
+    /// The size of the in-memory write buffer for each column family.
+    ///
+    /// If this isn't set, RocksDB's default (64MB) is used.
+    pub fn write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = Some(write_buffer_size);
+        self
+    }
+
+    /// Whether to lowercase the data part of keys on writes and lookups.
+    ///
+    /// With `CaseSensitivity::Insensitive`, observations for case variants of the same data are
+    /// merged into a single key. The original case is not stored and can't be recovered.
+    pub fn normalize_keys(mut self, normalize_keys: CaseSensitivity) -> Self {
+        self.normalize_keys = normalize_keys;
+        self
+    }
+
+    /// Expire entries after the given duration.
+    ///
+    /// Expired entries are only removed during compaction, so they may still be returned by
+    /// reads for some time after they expire. The expiration time is based on when a key was
+    /// last written, so every merge into a key resets its age, and a key's history is removed
+    /// all at once, not observation by observation. The TTL applies to the data and the index,
+    /// which means that index entries can expire independently of the data they point to.
+    ///
+    /// Metadata such as the value type and the incremental indexing watermark is kept from
+    /// expiring by rewriting it every time the database is opened in writeable mode, since the
+    /// RocksDB bindings don't support a separate TTL for its column family. This means that a
+    /// database shouldn't go longer than the TTL without being opened in writeable mode.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Whether writes are merged into existing values (the default) or replace them.
+    ///
+    /// With `WriteMode::Overwrite`, every write (including `merge_value` and batch writes)
+    /// replaces the stored value without using the merge operator, so the value type's `Add`
+    /// implementation is ignored, and empty values are written instead of being skipped.
+    pub fn write_mode(mut self, write_mode: WriteMode) -> Self {
+        self.write_mode = write_mode;
+        self
+    }
+
+    /// The compression algorithm for the `by_id` column family.
+    ///
+    /// If this isn't set, RocksDB's default is used.
+    pub fn compression(mut self, compression: DBCompressionType) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// The compression level for the `by_id` column family.
+    ///
+    /// The meaning of the level depends on the compression algorithm (for example zstd supports
+    /// levels from 1 to 22).
+    pub fn compression_level(mut self, compression_level: i32) -> Self {
+        self.compression_level = Some(compression_level);
+        self
+    }
+
+    /// Add a bloom filter with the given number of bits per key to the `by_id` column family.
+    ///
+    /// This can reduce disk reads for point lookups like `get_one`.
+    pub fn bloom_bits_per_key(mut self, bloom_bits_per_key: f64) -> Self {
+        self.bloom_bits_per_key = Some(bloom_bits_per_key);
+        self
+    }
+
+    /// Use a partitioned (two-level) index for the `by_id` column family.
+    ///
+    /// Index blocks are already prefix-compressed, but for datasets where keys are long or many
+    /// keys share prefixes, partitioning the index keeps only the top level in memory and loads
+    /// the smaller partitions on demand. This is disabled by default.
+    pub fn two_level_index(mut self, two_level_index: bool) -> Self {
+        self.two_level_index = two_level_index;
+        self
+    }
+
+    /// Whether to open the `index` column family (enabled by default).
+    ///
+    /// Databases that are never searched can disable the index to avoid the cost of opening and
+    /// maintaining it, in which case index operations will fail with [`Error::IndexDisabled`].
+    /// RocksDB requires all existing column families to be opened, so this can't be used to
+    /// open a database that already has an index.
+    pub fn with_index(mut self, with_index: bool) -> Self {
+        self.with_index = with_index;
+        self
+    }
+
+    /// Track the most recent timestamp for each index term (disabled by default).
+    ///
+    /// The timestamps come from `Value::last_timestamp` and are stored in a separate
+    /// `index_last_seen` column family, so the index itself is unchanged. They're recorded by
+    /// `make_index`, `index_id`, and writes with automatic indexing, and can be read with
+    /// [`Hkvdb::search_with_last_seen`]. Like the index, this column family must be opened if it exists.
+    pub fn index_last_seen(mut self, index_last_seen: bool) -> Self {
+        self.index_last_seen = index_last_seen;
+        self
+    }
+
+    /// Remove observations from before the cutoff.
+    ///
+    /// This only affects value types that track timestamps (see `Value::is_expired`). Values that
+    /// only contain old observations are removed during compaction, and value types that store
+    /// observations individually (such as `Set32`, see `Value::expire`) drop old observations
+    /// whenever they're merged, including when merge operands are combined during compaction.
+    /// Index entries are not updated.
+    ///
+    /// Because trimming happens in the merge operator, there are some limitations:
+    ///
+    /// * Every merge decodes and re-encodes the merged value, for all value types.
+    /// * Values that aren't merged again keep their old observations until they're removed
+    ///   entirely. This includes values written with `put_if_absent` or `WriteMode::Overwrite`
+    ///   and values that have already been fully compacted.
+    /// * Values may still be returned by reads for some time after they expire. Values whose
+    ///   observations have all been dropped are empty until they're removed by compaction, and
+    ///   these are skipped by `get`, `get_one`, and `get_sorted`.
+    pub fn expire_before(mut self, cutoff: u32) -> Self {
+        self.expire_before = Some(cutoff);
+        self
+    }
+
+    /// Report operation timings to the given sink.
+    pub fn metrics(mut self, metrics: Arc<dyn MetricsSink>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Report errors that can't be returned to the caller (such as merge failures) to the
+    /// given observer, in addition to logging them.
+    pub fn error_observer(mut self, error_observer: Arc<dyn ErrorObserver>) -> Self {
+        self.error_observer = Some(error_observer);
+        self
+    }
+}
+
+impl<M, V> HkvdbBuilder<M, V> {
+    /// Update the index on every write, so that `make_index` doesn't need to be run.
+    ///
+    /// Index entries are written in the same batch as the data they point to.
+    pub fn auto_index(mut self, case_sensitivity: CaseSensitivity) -> Self {
+        self.auto_index = Some(case_sensitivity);
+        self
+    }
+}
+
+impl<M: Mode + 'static, V: Value + 'static, K: Key + 'static> HkvdbBuilder<M, V, K> {
+    pub fn open<P: AsRef<Path>>(self, path: P) -> Result<Hkvdb<M, V, K>, Error> {
+        let mut options = Options::default();
+        options.create_missing_column_families(true);
+        options.create_if_missing(self.create_if_missing);
+
+        if self.enable_statistics {
+            options.enable_statistics();
+        }
+
+        if let Some(wal_recovery_mode) = self.wal_recovery_mode {
+            options.set_wal_recovery_mode(wal_recovery_mode);
+        }
+
+        if let Some(max_open_files) = self.max_open_files {
+            options.set_max_open_files(max_open_files);
+        }
+
+        let mut by_id_cf_block_options = BlockBasedOptions::default();
+        by_id_cf_block_options.set_data_block_index_type(DataBlockIndexType::BinaryAndHash);
+        by_id_cf_block_options
+            .set_block_cache(&rocksdb::Cache::new_lru_cache(self.block_cache_bytes)?);
+
+        if let Some(bloom_bits_per_key) = self.bloom_bits_per_key {
+            by_id_cf_block_options.set_bloom_filter(bloom_bits_per_key, false);
+        }
+
+        if self.two_level_index {
+            by_id_cf_block_options.set_index_type(BlockBasedIndexType::TwoLevelIndexSearch);
+        }
+
+        let mut by_id_cf_options = Options::default();
+        by_id_cf_options.set_block_based_table_factory(&by_id_cf_block_options);
+        let error_observer = self.error_observer.clone();
+        let expire_before = self.expire_before;
+        by_id_cf_options.set_merge_operator_associative(
+            "merge_by_id",
+            move |_key, existing_value, operands| {
+                Hkvdb::<M, V, K>::merge_by_id(
+                    error_observer.as_deref(),
+                    expire_before,
+                    existing_value,
+                    operands,
+                )
+            },
+        );
+        by_id_cf_options.set_prefix_extractor(SliceTransform::create_fixed_prefix(K::LEN));
+
+        if let Some(write_buffer_size) = self.write_buffer_size {
+            by_id_cf_options.set_write_buffer_size(write_buffer_size);
+        }
+
+        if let Some(compression) = self.compression {
+            by_id_cf_options.set_compression_type(compression);
+        }
+
+        if let Some(cutoff) = self.expire_before {
+            by_id_cf_options.set_compaction_filter(
+                "expire_before",
+                move |_level, _key, value_bytes: &[u8]| match V::try_from(value_bytes) {
+                    Ok(value) if value.is_empty() || value.is_expired(cutoff) => Decision::Remove,
+                    Ok(_) => Decision::Keep,
+                    Err(error) => {
+                        log::error!("Error during expiry compaction filter: {:?}", error);
+                        Decision::Keep
+                    }
+                },
+            );
+        }
+
+        if let Some(compression_level) = self.compression_level {
+            // The other values are RocksDB's defaults.
+            by_id_cf_options.set_compression_options(-14, compression_level, 0, 0);
+        }
+
+        let by_id_cf = ColumnFamilyDescriptor::new("by_id", by_id_cf_options);
+        let meta_cf = ColumnFamilyDescriptor::new("meta", Options::default());
+
+        let mut column_families = vec![by_id_cf, meta_cf];
+
+        if self.with_index {
+            let mut index_cf_block_options = BlockBasedOptions::default();
+            index_cf_block_options.set_data_block_index_type(DataBlockIndexType::BinaryAndHash);
+
+            let mut index_cf_options = Options::default();
+            index_cf_options.set_block_based_table_factory(&index_cf_block_options);
+
+            if let Some(write_buffer_size) = self.write_buffer_size {
+                index_cf_options.set_write_buffer_size(write_buffer_size);
+            }
+
+            let error_observer = self.error_observer.clone();
+            index_cf_options.set_merge_operator_associative(
+                "merge_index",
+                move |_key, existing_value, operands| {
+                    Hkvdb::<M, V, K>::merge_index(
+                        error_observer.as_deref(),
+                        existing_value,
+                        operands,
+                    )
+                },
+            );
+
+            column_families.push(ColumnFamilyDescriptor::new("index", index_cf_options));
+
+            if self.index_last_seen {
+                let mut last_seen_cf_options = Options::default();
+                let error_observer = self.error_observer;
+                last_seen_cf_options.set_merge_operator_associative(
+                    "merge_last_seen",
+                    move |_key, existing_value, operands| {
+                        Hkvdb::<M, V, K>::merge_last_seen(
+                            error_observer.as_deref(),
+                            existing_value,
+                            operands,
+                        )
+                    },
+                );
+
+                column_families.push(ColumnFamilyDescriptor::new(
+                    "index_last_seen",
+                    last_seen_cf_options,
+                ));
+            }
+        }
+
+        let db = match self.ttl {
+            Some(ttl) => DB::open_cf_descriptors_with_ttl(&options, path, column_families, ttl)?,
+            None => DB::open_cf_descriptors(&options, path, column_families)?,
+        };
+
+        check_value_type::<M, V>(&db)?;
+
+        // RocksDB applies the TTL to every column family, so the metadata is rewritten to reset
+        // its age instead.
+        if self.ttl.is_some() && !M::is_read_only() {
+            refresh_meta(&db)?;
+        }
+
+        Ok(Hkvdb {
+            db: Arc::new(db),
+            options,
+            normalize_keys: self.normalize_keys,
+            auto_index: self.auto_index,
+            write_mode: self.write_mode,
+            metrics: self.metrics,
+            _mode: PhantomData,
+            _merge: PhantomData,
+            _key: PhantomData,
+        })
+    }
+}
+
+impl<M, V: Value, K: Key> Hkvdb<M, V, K> {
+    fn make_data_key(&self, id: &K, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self.normalize_keys {
+            CaseSensitivity::Sensitive => Ok(KeyCodec::encode(id, data)),
+            CaseSensitivity::Insensitive => Ok(KeyCodec::encode(
+                id,
+                std::str::from_utf8(data)?.to_lowercase().as_bytes(),
+            )),
+        }
+    }
+
+    pub fn get_one_raw(&self, id: K, data: &[u8]) -> Result<Option<V>, Error> {
+        let _timer = self.timer(MetricsSink::on_get);
+        let key = self.make_data_key(&id, data)?;
+
+        match self.db.get_pinned_cf(self.by_id_cf(), key)? {
+            // Values can be empty after their observations expire, and are treated as missing.
+            Some(bytes) => Ok(Some(V::try_from(bytes.as_ref())?).filter(|value| !value.is_empty())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_one(&self, id: K, data: &str) -> Result<Option<V>, Error> {
+        self.get_one_raw(id, data.as_bytes())
+    }
+
+    pub fn get_raw(&self, id: K) -> Result<HashMap<Vec<u8>, V>, Error> {
+        let _timer = self.timer(MetricsSink::on_get);
+        let prefix = KeyCodec::prefix(&id);
+        let mut results = HashMap::new();
+        let iter = self.db.prefix_iterator_cf(self.by_id_cf(), &prefix);
+
+        for result in iter {
+            let (key, value_bytes) = result?;
+
+            let (id_bytes, data) = KeyCodec::<K>::split(&key)?;
+
+            if id_bytes == prefix {
+                let value = V::try_from(value_bytes.as_ref())?;
+
+                if !value.is_empty() {
+                    results.insert(data.to_vec(), value);
+                }
+            } else {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub fn get(&self, id: K) -> Result<HashMap<String, V>, Error> {
+        let as_bytes = self.get_raw(id)?;
+        let mut result = HashMap::with_capacity(as_bytes.len());
+
+        for (k, v) in as_bytes {
+            result.insert(String::from_utf8(k).map_err(Error::invalid_utf8_key)?, v);
+        }
+
+        Ok(result)
+    }
+
+    /// Get all values for an id in ascending key order.
+    pub fn get_sorted(&self, id: K) -> Result<Vec<(String, V)>, Error> {
+        let _timer = self.timer(MetricsSink::on_get);
+        let prefix = KeyCodec::prefix(&id);
+        let mut results = vec![];
+        let iter = self.db.prefix_iterator_cf(self.by_id_cf(), &prefix);
+
+        for result in iter {
+            let (key, value_bytes) = result?;
+
+            let (id_bytes, data) = KeyCodec::<K>::split(&key)?;
+
+            if id_bytes == prefix {
+                let data = String::from_utf8(data.to_vec()).map_err(Error::invalid_utf8_key)?;
+                let value = V::try_from(value_bytes.as_ref())?;
+
+                if !value.is_empty() {
+                    results.push((data, value));
+                }
+            } else {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Get all values for an id, or `None` if there are no keys for the id.
+    pub fn get_opt(&self, id: K) -> Result<Option<HashMap<String, V>>, Error> {
+        let prefix = KeyCodec::prefix(&id);
+        let first = self
+            .db
+            .prefix_iterator_cf(self.by_id_cf(), &prefix)
+            .next()
+            .transpose()?;
+
+        match first {
+            Some((key, _)) if key.starts_with(&prefix) => self.get(id).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Get all values for an id whose keys start with the given prefix.
+    pub fn get_prefix(&self, id: K, key_prefix: &str) -> Result<HashMap<String, V>, Error> {
+        let start = self.make_data_key(&id, key_prefix.as_bytes())?;
+        let mut results = HashMap::new();
+        let iter = self.db.prefix_iterator_cf(self.by_id_cf(), &start);
+
+        for result in iter {
+            let (key, value_bytes) = result?;
+
+            if key.starts_with(&start) {
+                let (_, data) = KeyCodec::<K>::split(&key)?;
+                let value = V::try_from(value_bytes.as_ref())?;
+                results.insert(
+                    String::from_utf8(data.to_vec()).map_err(Error::invalid_utf8_key)?,
+                    value,
+                );
+            } else {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Count the distinct keys stored for an id without reading their values.
+    pub fn key_count(&self, id: K) -> Result<u64, Error> {
+        let prefix = KeyCodec::prefix(&id);
+        let mut count = 0;
+        let iter = self.db.prefix_iterator_cf(self.by_id_cf(), &prefix);
+
+        for result in iter {
+            let (key, _) = result?;
+
+            if key.starts_with(&prefix) {
+                count += 1;
+            } else {
+                break;
+            }
+        }
+
+        Ok(count)
+    }
+
+    pub fn iter_raw(&self) -> RawIterator<'_, V, K> {
+        RawIterator {
+            underlying: self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start),
+            _merge: PhantomData,
+            _key: PhantomData,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Result<(K, String, V), Error>> + '_ {
+        self.iter_raw().map(|result| result.and_then(decode_data))
+    }
+
+    /// Iterate over all rows with ids from `start` (inclusive) to `end` (exclusive).
+    pub fn iter_id_range(
+        &self,
+        start: K,
+        end: K,
+    ) -> impl Iterator<Item = Result<(K, String, V), Error>> + '_ {
+        let start = KeyCodec::prefix(&start);
+        let end = KeyCodec::prefix(&end);
+
+        self.db
+            .iterator_cf(
+                self.by_id_cf(),
+                IteratorMode::From(&start, Direction::Forward),
+            )
+            // Keys are prefixed by their ids, so this only stops at ids that are at least `end`.
+            .take_while(
+                move |result| !matches!(result, Ok((key, _)) if key.as_ref() >= end.as_slice()),
+            )
+            .map(|result| {
+                result
+                    .map_err(Error::from)
+                    .and_then(|(key, value_bytes)| RawIterator::parse(&key, &value_bytes))
+                    .and_then(decode_data)
+            })
+    }
+
+    /// Iterate over all ids along with all of their keys and values.
+    ///
+    /// This is equivalent to calling `get` for every id, but only requires a single scan.
+    pub fn iter_grouped(&self) -> GroupedIterator<'_, V, K> {
+        GroupedIterator {
+            underlying: self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start),
+            pending: None,
+            _merge: PhantomData,
+            _key: PhantomData,
+        }
+    }
+
+    /// Find the ids that have at least one value satisfying the predicate.
+    ///
+    /// This scans the entire database. The ids are returned in order without duplicates.
+    pub fn filter_ids<F: Fn(&V) -> bool>(&self, pred: F) -> Result<Vec<K>, Error> {
+        let mut ids = vec![];
+        let mut last_match: Option<Vec<u8>> = None;
+
+        for result in self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start) {
+            let (key, value_bytes) = result?;
+            let (id_bytes, _) = KeyCodec::<K>::split(&key)?;
+
+            // Keys are sorted, so once an id matches, the rest of its values can be skipped.
+            if last_match.as_deref() != Some(id_bytes) && pred(&V::try_from(value_bytes.as_ref())?)
+            {
+                ids.push(K::from_bytes(id_bytes)?);
+                last_match = Some(id_bytes.to_vec());
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Find the keys whose stored values are invalid, along with their ids.
+    ///
+    /// This scans the entire database, checking values with `Value::validate`, which for most
+    /// built-in value types doesn't require deserializing them.
+    pub fn verify(&self) -> Result<Vec<(K, Vec<u8>)>, Error> {
+        let mut invalid = vec![];
+
+        for result in self.db.iterator_cf(self.by_id_cf(), IteratorMode::Start) {
+            let (key, value_bytes) = result?;
+
+            if V::validate(&value_bytes).is_err() {
+                let (id, data) = KeyCodec::decode(&key)?;
+                invalid.push((id, data.to_vec()));
+            }
+        }
+
+        Ok(invalid)
+    }
+
+    fn merge_by_id(
+        error_observer: Option<&dyn ErrorObserver>,
+        expire_before: Option<u32>,
+        existing_value: Option<&[u8]>,
+        operands: &MergeOperands,
+    ) -> Option<Vec<u8>> {
+        let merged =
+            V::merge(existing_value, operands.iter()).unwrap_or_else(|(error, fallback_value)| {
+                // The RocksDb library doesn't let us fail in a merge, so we just log the
+                // error and use the last value before the error. This should never happen.
+                log::error!("Error during aggregation in merge: {:?}", error);
+
+                if let Some(error_observer) = error_observer {
+                    error_observer.on_merge_error("by_id", &error);
+                }
+
+                fallback_value
+            });
+
+        // Compaction filters can only replace values with static data, so expired observations
+        // are removed here instead, when the merged value is rewritten.
+        match (merged, expire_before) {
+            (Some(bytes), Some(cutoff)) => match V::try_from(bytes.as_slice()) {
+                Ok(value) => Some(value.expire(cutoff).into()),
+                Err(_) => Some(bytes),
+            },
+            (merged, _) => merged,
+        }
+    }
+
+    fn merge_index(
+        error_observer: Option<&dyn ErrorObserver>,
+        existing_value: Option<&[u8]>,
+        operands: &MergeOperands,
+    ) -> Option<Vec<u8>> {
+        Set64::merge(existing_value, operands.iter()).unwrap_or_else(|(error, fallback_value)| {
+            // The RocksDb library doesn't let us fail in a merge, so we just log the
+            // error and use the last value before the error. This should never happen.
+            log::error!("Error during aggregation in index merge: {:?}", error);
+
+            if let Some(error_observer) = error_observer {
+                error_observer.on_merge_error("index", &error);
+            }
+
+            fallback_value
+        })
+    }
+
+    fn merge_last_seen(
+        error_observer: Option<&dyn ErrorObserver>,
+        existing_value: Option<&[u8]>,
+        operands: &MergeOperands,
+    ) -> Option<Vec<u8>> {
+        let mut last_seen = None;
+
+        for bytes in existing_value.into_iter().chain(operands.iter()) {
+            match <[u8; 4]>::try_from(bytes) {
+                Ok(timestamp) => last_seen = last_seen.max(Some(u32::from_be_bytes(timestamp))),
+                Err(_) => {
+                    // As above, invalid operands can only be logged and skipped.
+                    let error = Error::invalid_value(bytes);
+                    log::error!("Error during last-seen merge: {:?}", error);
+
+                    if let Some(error_observer) = error_observer {
+                        error_observer.on_merge_error("index_last_seen", &error);
+                    }
+                }
+            }
+        }
+
+        last_seen.map(|timestamp: u32| timestamp.to_be_bytes().to_vec())
+    }
+}
+
+impl<M, V: Value> Hkvdb<M, V> {
+    /// Call a function on every row, using one thread for each available core.
+    ///
+    /// The range of ids is split into equal parts, so the work will be unbalanced if the ids
+    /// aren't evenly distributed. Rows are visited in order within each part, but the parts are
+    /// processed concurrently.
+    pub fn par_for_each<F: Fn(u64, &[u8], V) + Sync>(&self, f: F) -> Result<(), Error> {
+        let (min, max) = match self.id_bounds()? {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let db = self.db.as_ref();
+        let f = &f;
+
+        std::thread::scope(|scope| {
+            let handles = split_id_range(min, max, threads)
+                .into_iter()
+                .map(|(first, last)| {
+                    scope.spawn(move || {
+                        let start = KeyCodec::prefix(&first);
+                        let iter = db.iterator_cf(
+                            db.cf_handle("by_id").unwrap(),
+                            IteratorMode::From(&start, Direction::Forward),
+                        );
+
+                        for result in iter {
+                            let (key, value_bytes) = result?;
+                            let (id, data, value) = RawIterator::<V>::parse(&key, &value_bytes)?;
+
+                            if id > last {
+                                break;
+                            }
+
+                            f(id, &data, value);
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+        })
+    }
+}
+
+impl<M, K: Key> Hkvdb<M, Range32, K> {
+    /// The earliest timestamp for the key, if there is a value for it.
+    pub fn first_observed(&self, id: K, data: &str) -> Result<Option<u32>, Error> {
+        Ok(self.get_one(id, data)?.map(|range| range.first()))
+    }
+
+    /// The latest timestamp for the key, if there is a value for it.
+    pub fn last_observed(&self, id: K, data: &str) -> Result<Option<u32>, Error> {
+        Ok(self.get_one(id, data)?.map(|range| range.last()))
+    }
+}
+
+pub struct RawIterator<'a, V, K = u64> {
+    underlying: DBIterator<'a>,
+    _merge: PhantomData<V>,
+    _key: PhantomData<K>,
+}
+
+impl<'a, V: Value, K: Key> RawIterator<'a, V, K> {
+    fn parse(key: &[u8], value_bytes: &[u8]) -> <Self as Iterator>::Item {
+        let (id, data) = KeyCodec::decode(key)?;
+
+        let value = V::try_from(value_bytes)?;
+
+        Ok((id, data.to_vec(), value))
+    }
+}
+
+impl<'a, V: Value, K: Key> Iterator for RawIterator<'a, V, K> {
+    type Item = Result<(K, Vec<u8>, V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.underlying.next().map(|result| {
+            result
+                .map_err(Error::from)
+                .and_then(|(key, value_bytes)| Self::parse(&key, &value_bytes))
+        })
+    }
+}
+
+type Row = (Box<[u8]>, Box<[u8]>);
+
+/// Writes values to an SST file for bulk loading.
+///
+/// Keys must be added in strictly increasing order (by id and then by data, after any case
+/// normalization), and each key may only be added once.
+pub struct SstBuilder<'a, V, K = u64> {
+    db: &'a Hkvdb<Writeable, V, K>,
+    writer: SstFileWriter<'a>,
+}
+
+impl<'a, V: Value, K: Key> SstBuilder<'a, V, K> {
+    pub fn put<IV: Into<V>>(&mut self, id: K, data: &str, value: IV) -> Result<(), Error> {
+        let key = self.db.make_data_key(&id, data.as_bytes())?;
+        let bytes: Vec<u8> = value.into().into();
+
+        Ok(self.writer.put(key, bytes)?)
+    }
+
+    /// Finish writing the file.
+    pub fn finish(mut self) -> Result<(), Error> {
+        Ok(self.writer.finish()?)
+    }
+}
+
+/// Groups the rows of the `by_id` column family by id.
+///
+/// Rows are sorted by id, so all rows for an id are consecutive.
+pub struct GroupedIterator<'a, V, K = u64> {
+    underlying: DBIterator<'a>,
+    pending: Option<Row>,
+    _merge: PhantomData<V>,
+    _key: PhantomData<K>,
+}
+
+impl<'a, V: Value, K: Key> GroupedIterator<'a, V, K> {
+    fn group(&mut self, key: &[u8], value_bytes: &[u8]) -> <Self as Iterator>::Item {
+        let (prefix, data) = KeyCodec::<K>::split(key)?;
+        let (id, _) = KeyCodec::decode(key)?;
+        let mut values = HashMap::new();
+
+        Self::insert(&mut values, data, value_bytes)?;
+
+        for result in self.underlying.by_ref() {
+            let (next_key, next_value_bytes) = result?;
+            let (next_prefix, next_data) = KeyCodec::<K>::split(&next_key)?;
+
+            if next_prefix == prefix {
+                Self::insert(&mut values, next_data, &next_value_bytes)?;
+            } else {
+                self.pending = Some((next_key, next_value_bytes));
+                break;
+            }
+        }
+
+        Ok((id, values))
+    }
+
+    fn insert(
+        values: &mut HashMap<String, V>,
+        data: &[u8],
+        value_bytes: &[u8],
+    ) -> Result<(), Error> {
+        let data = String::from_utf8(data.to_vec()).map_err(Error::invalid_utf8_key)?;
+        values.insert(data, V::try_from(value_bytes)?);
+
+        Ok(())
+    }
+}
+
+impl<'a, V: Value, K: Key> Iterator for GroupedIterator<'a, V, K> {
+    type Item = Result<(K, HashMap<String, V>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value_bytes) = match self.pending.take() {
+            Some(row) => row,
+            None => match self.underlying.next()? {
+                Ok(row) => row,
+                Err(error) => return Some(Err(error.into())),
+            },
+        };
+
+        Some(self.group(&key, &value_bytes))
+    }
+}
+
+/// Iterates over the ids in a serialized `Set64` without copying it.
+pub struct IdIterator<'a> {
+    bytes: Option<DBPinnableSlice<'a>>,
+    offset: usize,
+}
+
+impl<'a> IdIterator<'a> {
+    fn new(bytes: Option<DBPinnableSlice<'a>>) -> Result<Self, Error> {
+        if let Some(bytes) = &bytes {
+            if !bytes.chunks_exact(8).remainder().is_empty() {
+                return Err(Error::invalid_value(bytes));
+            }
+        }
+
+        Ok(Self { bytes, offset: 0 })
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes
+            .as_ref()
+            .map_or(0, |bytes| (bytes.len() - self.offset) / 8)
+    }
+}
+
+impl<'a> Iterator for IdIterator<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.bytes.as_ref()?;
+        let next = bytes.get(self.offset..self.offset + 8)?;
+        self.offset += 8;
+
+        Some(u64::from_be_bytes(next.try_into().ok()?))
+    }
+
+    // Skips directly to the requested id without decoding the ones before it.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.offset = self
+            .offset
+            .saturating_add(n.saturating_mul(8))
+            .min(self.bytes.as_ref().map_or(0, |bytes| bytes.len()));
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for IdIterator<'a> {}
+
+/// Merges sorted id iterators, yielding each id once.
+pub struct UnionIterator<'a> {
+    iters: Vec<IdIterator<'a>>,
+    heap: BinaryHeap<Reverse<(u64, usize)>>,
+    last: Option<u64>,
+}
+
+impl<'a> UnionIterator<'a> {
+    fn new(mut iters: Vec<IdIterator<'a>>) -> Self {
+        let mut heap = BinaryHeap::with_capacity(iters.len());
+
+        for (i, iter) in iters.iter_mut().enumerate() {
+            if let Some(id) = iter.next() {
+                heap.push(Reverse((id, i)));
+            }
+        }
+
+        Self {
+            iters,
+            heap,
+            last: None,
+        }
+    }
+}
+
+impl<'a> Iterator for UnionIterator<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Reverse((id, i)) = self.heap.pop()?;
+
+            if let Some(next) = self.iters[i].next() {
+                self.heap.push(Reverse((next, i)));
+            }
+
+            if self.last != Some(id) {
+                self.last = Some(id);
+                return Some(id);
+            }
+        }
+    }
+}
+
+impl<V: Value> Hkvdb<Writeable, V> {
+    pub fn make_index(&self, case_sensitivity: CaseSensitivity) -> Result<(), Error> {
+        self.make_index_batched(case_sensitivity, INDEX_BATCH_SIZE)
+    }
+
+    /// Build the index, writing index entries in batches of at most `batch_size` merges.
+    pub fn make_index_batched(
+        &self,
+        case_sensitivity: CaseSensitivity,
+        batch_size: usize,
+    ) -> Result<(), Error> {
+        index_range::<V>(&self.db, 0, u64::MAX, case_sensitivity, batch_size, false)
+    }
+
+    /// Index all ids greater than or equal to `since_id`, recording the progress as a watermark.
+    ///
+    /// The watermark is written along with each batch of index entries, so if indexing is
+    /// interrupted, it can be resumed by passing the value from [`Hkvdb::index_watermark`]. The
+    /// id at the watermark may be indexed twice, which has no effect since index entries are
+    /// merged as sets. When indexing finishes, the watermark is set to one more than the largest
+    /// id, so that later calls only index new ids.
+    pub fn make_index_incremental(
+        &self,
+        since_id: u64,
+        case_sensitivity: CaseSensitivity,
+    ) -> Result<(), Error> {
+        index_range::<V>(
+            &self.db,
+            since_id,
+            u64::MAX,
+            case_sensitivity,
+            INDEX_BATCH_SIZE,
+            true,
+        )
+    }
+
+    /// Build the index using multiple threads, each of which indexes a contiguous range of ids.
+    ///
+    /// Index merges are associative, so the result is the same as for `make_index`.
+    pub fn make_index_parallel(
+        &self,
+        case_sensitivity: CaseSensitivity,
+        threads: usize,
+    ) -> Result<(), Error> {
+        let (min, max) = match self.id_bounds()? {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+
+        let db = self.db.as_ref();
+
+        std::thread::scope(|scope| {
+            let handles = split_id_range(min, max, threads)
+                .into_iter()
+                .map(|(first, last)| {
+                    scope.spawn(move || {
+                        index_range::<V>(db, first, last, case_sensitivity, INDEX_BATCH_SIZE, false)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+        })
+    }
+
+    /// Remove an id from the index entry for a single term.
+    ///
+    /// This reads the current entry and writes back the updated set (or deletes the entry if it
+    /// becomes empty), so concurrent index merges for the same term may be lost.
+    pub fn index_remove(
+        &self,
+        term: &str,
+        id: u64,
+        case_sensitivity: CaseSensitivity,
+    ) -> Result<(), Error> {
+        let index_key = make_index_key(term.as_bytes(), case_sensitivity)?;
+
+        if let Some(bytes) = self.db.get_pinned_cf(self.index_cf()?, &index_key)? {
+            let ids = Set64::try_from(bytes.as_ref())?;
+            let remaining = ids.difference(&Set64::singleton(id));
+
+            if remaining.values().is_empty() {
+                self.db.delete_cf(self.index_cf()?, &index_key)?;
+            } else {
+                let remaining_bytes: Vec<u8> = remaining.into();
+                self.db
+                    .put_cf(self.index_cf()?, &index_key, remaining_bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write index entries (for example from [`Hkvdb::iter_index`]) into the index.
+    ///
+    /// Entries are merged, so ids for terms that are already in the index are added to them.
+    pub fn restore_index<I: IntoIterator<Item = (Vec<u8>, Set64)>>(
+        &self,
+        entries: I,
+    ) -> Result<(), Error> {
+        let mut wb = WriteBatch::default();
+
+        for (key, ids) in entries {
+            wb.merge_cf(self.index_cf()?, key, Vec::<u8>::from(ids));
+
+            if wb.len() >= INDEX_BATCH_SIZE {
+                self.db.write(std::mem::take(&mut wb))?;
+            }
+        }
+
+        if !wb.is_empty() {
+            self.db.write(wb)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add index entries for all keys currently stored for a single id.
+    ///
+    /// Note that this only adds entries, so if a key has been removed from the id, the index
+    /// will still contain a stale entry for it.
+    pub fn index_id(&self, id: u64, case_sensitivity: CaseSensitivity) -> Result<(), Error> {
+        let prefix = KeyCodec::prefix(&id);
+        let id_bytes: Vec<u8> = Set64::singleton(id).into();
+        let iter = self.db.prefix_iterator_cf(self.by_id_cf(), &prefix);
+        let last_seen_cf = self.db.cf_handle("index_last_seen");
+
+        for result in iter {
+            let (id_data_key, value_bytes) = result?;
+            let (id_prefix, data) = KeyCodec::<u64>::split(&id_data_key)?;
+
+            if id_prefix == prefix {
+                let index_key = make_index_key(data, case_sensitivity)?;
+
+                self.db.merge_cf(self.index_cf()?, &index_key, &id_bytes)?;
+
+                if let Some(last_seen_cf) = last_seen_cf {
+                    if let Some(timestamp) = V::try_from(value_bytes.as_ref())?.last_timestamp() {
+                        self.db
+                            .merge_cf(last_seen_cf, &index_key, timestamp.to_be_bytes())?;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<V: Value> Hkvdb<Writeable, V> {
+    /// Write a value and add an index entry for its key atomically.
+    pub fn put_indexed(
+        &self,
+        id: u64,
+        data: &str,
+        value: V,
+        case_sensitivity: CaseSensitivity,
+    ) -> Result<(), Error> {
+        if self.skips_write(&value) {
+            return Ok(());
+        }
+
+        let _timer = self.timer(MetricsSink::on_put);
+        let key = self.make_data_key(&id, data.as_bytes())?;
+        let (_, normalized_data) = KeyCodec::<u64>::split(&key)?;
+        let index_key = make_index_key(normalized_data, case_sensitivity)?;
+        let id_bytes: Vec<u8> = Set64::singleton(id).into();
+        let mut wb = WriteBatch::default();
+
+        wb.merge_cf(self.index_cf()?, &index_key, id_bytes);
+        self.last_seen_into_batch(&mut wb, &index_key, &value);
+        self.merge_into_batch(&mut wb, key, value)?;
+
+        Ok(self.db.write(wb)?)
+    }
+}
+
+impl<V: Value, K: Key> Hkvdb<Writeable, V, K> {
+    pub fn put_raw<IV: Into<V>>(&self, id: K, data: &[u8], value: IV) -> Result<(), Error> {
+        let _timer = self.timer(MetricsSink::on_put);
+        let key = self.make_data_key(&id, data)?;
+        let mut wb = WriteBatch::default();
+        self.merge_into_batch(&mut wb, key, value.into())?;
+        Ok(self.db.write(wb)?)
+    }
+
+    pub fn put_raw_batch<'a, IV: Into<V>, I: IntoIterator<Item = (K, &'a [u8], IV)>>(
+        &'a self,
+        batch: I,
+    ) -> Result<(), Error> {
+        let _timer = self.timer(MetricsSink::on_put);
+        let mut wb = WriteBatch::default();
+
+        for (id, data, value) in batch {
+            let key = self.make_data_key(&id, data)?;
+            self.merge_into_batch(&mut wb, key, value.into())?;
+        }
+
+        Ok(self.db.write(wb)?)
+    }
+
+    pub fn put<IV: Into<V>>(&self, id: K, data: &str, value: IV) -> Result<(), Error> {
+        self.put_raw(id, data.as_bytes(), value)
+    }
+
+    /// Merge a value and return the result of merging it with any existing value.
+    ///
+    /// The write and the read are not atomic, so the result may include concurrent writes.
+    pub fn put_and_get(&self, id: K, data: &str, value: V) -> Result<V, Error> {
+        let _timer = self.timer(MetricsSink::on_put);
+        let key = self.make_data_key(&id, data.as_bytes())?;
+
+        // Empty values aren't merged, so we keep the value in case there's nothing stored.
+        let unwritten = if self.skips_write(&value) {
+            Some(value)
+        } else {
+            let mut wb = WriteBatch::default();
+            self.merge_into_batch(&mut wb, key.clone(), value)?;
+            self.db.write(wb)?;
+            None
+        };
+
+        match self.db.get_pinned_cf(self.by_id_cf(), &key)? {
+            Some(bytes) => V::try_from(bytes.as_ref()),
+            // If the value was written, this only happens if the key was deleted concurrently.
+            None => unwritten.ok_or(Error::InvalidKey(key)),
+        }
+    }
+
+    /// Write a value only if there's no value for the key, returning whether it was written.
+    ///
+    /// The check and the write are not atomic, so if another writer writes the same key
+    /// concurrently, its value may be overwritten. This method should only be used when there's
+    /// a single writer.
+    pub fn put_if_absent(&self, id: K, data: &str, value: V) -> Result<bool, Error> {
+        let key = self.make_data_key(&id, data.as_bytes())?;
+
+        if self.skips_write(&value) || self.db.get_pinned_cf(self.by_id_cf(), &key)?.is_some() {
+            return Ok(false);
+        }
+
+        let _timer = self.timer(MetricsSink::on_put);
+        let mut wb = WriteBatch::default();
+        self.index_into_batch(&mut wb, &key, &value)?;
+        wb.put_cf(self.by_id_cf(), key, value.into());
+        self.db.write(wb)?;
+
+        Ok(true)
+    }
+
+    /// Merge an already-aggregated value into the value stored for a key.
+    pub fn merge_value(&self, id: K, data: &str, value: V) -> Result<(), Error> {
+        let _timer = self.timer(MetricsSink::on_merge);
+        let key = self.make_data_key(&id, data.as_bytes())?;
+        let mut wb = WriteBatch::default();
+        self.merge_into_batch(&mut wb, key, value)?;
+        Ok(self.db.write(wb)?)
+    }
+
+    pub fn put_batch<S: AsRef<str>, IV: Into<V>, I: IntoIterator<Item = (K, S, IV)>>(
+        &self,
+        batch: I,
+    ) -> Result<(), Error> {
+        let _timer = self.timer(MetricsSink::on_put);
+        let mut wb = WriteBatch::default();
+
+        for (id, data, value) in batch {
+            let key = self.make_data_key(&id, data.as_ref().as_bytes())?;
+            self.merge_into_batch(&mut wb, key, value.into())?;
+        }
+
+        Ok(self.db.write(wb)?)
+    }
+
+    /// Write a batch in chunks of at most `chunk_size` items, to keep memory usage bounded.
+    pub fn put_batch_chunked<S: AsRef<str>, IV: Into<V>, I: IntoIterator<Item = (K, S, IV)>>(
+        &self,
+        batch: I,
+        chunk_size: usize,
+    ) -> Result<(), Error> {
+        let _timer = self.timer(MetricsSink::on_put);
+        let mut wb = WriteBatch::default();
+
+        for (id, data, value) in batch {
+            let key = self.make_data_key(&id, data.as_ref().as_bytes())?;
+            self.merge_into_batch(&mut wb, key, value.into())?;
+
+            if wb.len() >= chunk_size {
+                self.db.write(std::mem::take(&mut wb))?;
+            }
+        }
+
+        if !wb.is_empty() {
+            self.db.write(wb)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a builder for an SST file that can be loaded with [`Hkvdb::ingest_sorted`].
+    pub fn sst_builder<P: AsRef<Path>>(&self, sst_path: P) -> Result<SstBuilder<'_, V, K>, Error> {
+        let writer = SstFileWriter::create(&self.options);
+        writer.open(sst_path)?;
+
+        Ok(SstBuilder { db: self, writer })
+    }
+
+    /// Load an SST file created with [`Hkvdb::sst_builder`] into the database.
+    ///
+    /// Ingestion bypasses the merge operator, so values in the file replace any existing values
+    /// for the same keys, and callers must pre-merge values for duplicate keys before adding
+    /// them to the file. Index entries are not added, even if automatic indexing is enabled.
+    pub fn ingest_sorted<P: AsRef<Path>>(&self, sst_path: P) -> Result<(), Error> {
+        Ok(self
+            .db
+            .ingest_external_file_cf(self.by_id_cf(), vec![sst_path])?)
+    }
+
+    /// Write the contents of the write-ahead log buffer to disk, optionally syncing it.
+    ///
+    /// This doesn't flush memtables, so it's much cheaper than a full flush.
+    pub fn flush_wal(&self, sync: bool) -> Result<(), Error> {
+        Ok(self.db.flush_wal(sync)?)
+    }
+
+    /// Remove all data and index entries, along with the incremental indexing watermark.
+    ///
+    /// Column families can't be dropped while the database handle is shared, so instead this
+    /// deletes the full key range of each column family with a range deletion and then compacts
+    /// it, which is still much faster than deleting keys individually. Writes that happen
+    /// concurrently with a call to this method may or may not be removed.
+    pub fn clear(&self) -> Result<(), Error> {
+        self.db
+            .delete_cf(self.cf_handle("meta")?, INDEX_WATERMARK_KEY)?;
+
+        for cf in std::iter::once(self.by_id_cf())
+            .chain(self.index_cf().ok())
+            .chain(self.db.cf_handle("index_last_seen"))
+        {
+            let first = self.db.iterator_cf(cf, IteratorMode::Start).next();
+            let last = self.db.iterator_cf(cf, IteratorMode::End).next();
+
+            if let (Some(first), Some(last)) = (first, last) {
+                let (first, _) = first?;
+                let (last, _) = last?;
+
+                // The end of a range deletion is exclusive.
+                self.db.delete_range_cf(cf, &first, &last)?;
+                self.db.delete_cf(cf, &last)?;
+                self.db.compact_range_cf(cf, Some(&first), Some(&last));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a merge for a data key to a batch, along with an index entry if automatic indexing
+    /// is enabled.
+    ///
+    /// Nothing is added for empty values when merging, since they would have no effect.
+    fn merge_into_batch(&self, wb: &mut WriteBatch, key: Vec<u8>, value: V) -> Result<(), Error> {
+        if self.skips_write(&value) {
+            return Ok(());
+        }
+
+        match self.write_mode {
+            WriteMode::Merge => {
+                self.index_into_batch(wb, &key, &value)?;
+                wb.merge_cf(self.by_id_cf(), key, value.into());
+            }
+            WriteMode::Overwrite => {
+                self.index_into_batch(wb, &key, &value)?;
+                wb.put_cf(self.by_id_cf(), key, value.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a write can be skipped, which is the case for empty values when merging. Empty
+    /// values are still written when overwriting, since they replace the stored value.
+    fn skips_write(&self, value: &V) -> bool {
+        matches!(self.write_mode, WriteMode::Merge) && value.is_empty()
+    }
+
+    /// Add an index entry for a data key to a batch if automatic indexing is enabled.
+    fn index_into_batch(&self, wb: &mut WriteBatch, key: &[u8], value: &V) -> Result<(), Error> {
+        if let Some(case_sensitivity) = self.auto_index {
+            // The builder only allows automatic indexing for `u64` ids.
+            let (id, data) = KeyCodec::<u64>::decode(key)?;
+            let index_key = make_index_key(data, case_sensitivity)?;
+            let id_bytes: Vec<u8> = Set64::singleton(id).into();
+
+            wb.merge_cf(self.index_cf()?, &index_key, id_bytes);
+            self.last_seen_into_batch(wb, &index_key, value);
+        }
+
+        Ok(())
+    }
+
+    /// Add the latest timestamp of a value for an index term to a batch if last-seen times are
+    /// tracked.
+    fn last_seen_into_batch(&self, wb: &mut WriteBatch, index_key: &[u8], value: &V) {
+        if let (Some(last_seen_cf), Some(timestamp)) =
+            (self.db.cf_handle("index_last_seen"), value.last_timestamp())
+        {
+            wb.merge_cf(last_seen_cf, index_key, timestamp.to_be_bytes());
+        }
+    }
+}
+
+/// Confirm that the database was created with the same value type, since the merge operators
+/// would otherwise silently combine values using the wrong semantics.
+///
+/// The value type's tag is recorded the first time a database is opened in writeable mode.
+fn check_value_type<M: Mode, V: Value>(db: &DB) -> Result<(), Error> {
+    let meta_cf = db.cf_handle("meta").unwrap();
+    let expected = V::type_tag();
+
+    match db.get_pinned_cf(meta_cf, VALUE_TYPE_KEY)? {
+        Some(found) => {
+            if found.as_ref() == expected.as_bytes() {
+                Ok(())
+            } else {
+                Err(Error::ValueTypeMismatch {
+                    expected: expected.into_owned(),
+                    found: String::from_utf8_lossy(&found).into_owned(),
+                })
+            }
+        }
+        None => {
+            if !M::is_read_only() {
+                db.put_cf(meta_cf, VALUE_TYPE_KEY, expected.as_bytes())?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Rewrite the metadata entries, which resets their age in a database with a TTL.
+fn refresh_meta(db: &DB) -> Result<(), Error> {
+    let meta_cf = db.cf_handle("meta").unwrap();
+    let mut wb = WriteBatch::default();
+
+    for result in db.iterator_cf(meta_cf, IteratorMode::Start) {
+        let (key, value) = result?;
+        wb.put_cf(meta_cf, key, value);
+    }
+
+    Ok(db.write(wb)?)
+}
+
+impl<K: Key> Hkvdb<Writeable, Range32, K> {
+    /// Merge ranges that have already been built into the database in a single batch.
+    pub fn put_ranges_batch<I: IntoIterator<Item = (K, String, Range32)>>(
+        &self,
+        batch: I,
+    ) -> Result<(), Error> {
+        self.put_batch(batch)
+    }
+}
+
+impl<V: Timestamps, K: Key> Hkvdb<Writeable, V, K> {
+    /// Record that the data was observed for the id at the given timestamp.
+    pub fn observe(&self, id: K, data: &str, timestamp: u32) -> Result<(), Error> {
+        self.put(id, data, timestamp)
+    }
+}
+
+/// Restore the most recent backup in a backup directory to a new database directory.
+pub fn restore_from_backup<P: AsRef<Path>, Q: AsRef<Path>>(
+    backup_dir: P,
+    db_dir: Q,
+) -> Result<(), Error> {
+    let mut engine = BackupEngine::open(&BackupEngineOptions::new(backup_dir)?, &Env::new()?)?;
+    engine.restore_from_latest_backup(&db_dir, &db_dir, &RestoreOptions::default())?;
+    Ok(())
+}
+
+/// Copy all values from one database into another, converting them with the given function.
+///
+/// Values are merged into the destination, so existing values there are combined with the
+/// migrated values (and the destination's key normalization and auto-indexing are applied).
+pub fn migrate<M, A: Value, B: Value, K: Key, F: Fn(A) -> B>(
+    src: &Hkvdb<M, A, K>,
+    dst: &Hkvdb<Writeable, B, K>,
+    f: F,
+) -> Result<(), Error> {
+    let mut wb = WriteBatch::default();
+
+    for result in src.iter_raw() {
+        let (id, data, value) = result?;
+        let key = dst.make_data_key(&id, &data)?;
+        dst.merge_into_batch(&mut wb, key, f(value))?;
+
+        if wb.len() >= INDEX_BATCH_SIZE {
+            dst.db.write(std::mem::take(&mut wb))?;
+        }
+    }
+
+    if !wb.is_empty() {
+        dst.db.write(wb)?;
+    }
+
+    Ok(())
+}
+
+/// Add index entries for all keys with ids between `first` and `last` (inclusive).
+///
+/// If `record_watermark` is true, the id of the last key in each batch is written to the `meta`
+/// column family along with the batch, and the id after the last indexed id is written at the
+/// end.
+fn index_range<V: Value>(
+    db: &DB,
+    first: u64,
+    last: u64,
+    case_sensitivity: CaseSensitivity,
+    batch_size: usize,
+    record_watermark: bool,
+) -> Result<(), Error> {
+    let start = KeyCodec::prefix(&first);
+    let iter = db.iterator_cf(
+        db.cf_handle("by_id").unwrap(),
+        IteratorMode::From(&start, Direction::Forward),
+    );
+    let cf = db.cf_handle("index").ok_or(Error::IndexDisabled)?;
+    let last_seen_cf = db.cf_handle("index_last_seen");
+    let meta_cf = db.cf_handle("meta").unwrap();
+    let mut wb = WriteBatch::default();
+    let mut last_indexed = None;
+
+    for result in iter {
+        let (id_data_key, value_bytes) = result?;
+        let (id, data) = KeyCodec::<u64>::decode(&id_data_key)?;
+
+        if id > last {
+            break;
+        }
+
+        let index_key = make_index_key(data, case_sensitivity)?;
+        let id_bytes: Vec<u8> = Set64::singleton(id).into();
+
+        wb.merge_cf(cf, &index_key, &id_bytes);
+
+        // Values are only decoded if last-seen times are tracked.
+        if let Some(last_seen_cf) = last_seen_cf {
+            if let Some(timestamp) = V::try_from(value_bytes.as_ref())?.last_timestamp() {
+                wb.merge_cf(last_seen_cf, &index_key, timestamp.to_be_bytes());
+            }
+        }
+        last_indexed = Some(id);
+
+        if wb.len() >= batch_size {
+            if record_watermark {
+                wb.put_cf(meta_cf, INDEX_WATERMARK_KEY, id.to_be_bytes());
+            }
+
+            db.write(std::mem::take(&mut wb))?;
+        }
+    }
+
+    if record_watermark {
+        // If nothing was indexed, the watermark stays at the first id.
+        let next = last_indexed.map_or(first, |id: u64| id.saturating_add(1));
+
+        wb.put_cf(meta_cf, INDEX_WATERMARK_KEY, next.to_be_bytes());
+    }
+
+    if !wb.is_empty() {
+        db.write(wb)?;
+    }
+
+    Ok(())
+}
+
+/// Split the ids from `min` to `max` (inclusive) into at most `parts` non-empty ranges.
+fn split_id_range(min: u64, max: u64, parts: usize) -> Vec<(u64, u64)> {
+    // Computed in 128 bits since the full range of ids doesn't fit in 64.
+    let parts = parts.max(1) as u128;
+    let span = u128::from(max - min) + 1;
+    let starts = (0..=parts)
+        .map(|i| u128::from(min) + span * i / parts)
+        .collect::<Vec<_>>();
+
+    starts
+        .windows(2)
+        .filter(|range| range[0] < range[1])
+        .map(|range| (range[0] as u64, (range[1] - 1) as u64))
+        .collect()
+}
+
+/// Decode the data part of a row as UTF-8.
+fn decode_data<K, V>((id, bytes, value): (K, Vec<u8>, V)) -> Result<(K, String, V), Error> {
+    Ok((
+        id,
+        String::from_utf8(bytes).map_err(Error::invalid_utf8_key)?,
+        value,
+    ))
+}
+
+/// Find the count for a ticker in a RocksDB statistics dump.
+///
+/// Ticker lines look like `rocksdb.block.cache.hit COUNT : 123`.
+fn parse_ticker(statistics: &str, name: &str) -> Option<u64> {
+    statistics
+        .lines()
+        .filter_map(parse_ticker_line)
+        .find_map(|(line_name, count)| (line_name == name).then_some(count))
+}
+
+/// Parse a ticker line (for example `rocksdb.block.cache.miss COUNT : 5`) into a name and count.
+fn parse_ticker_line(line: &str) -> Option<(&str, u64)> {
+    let mut parts = line.split_whitespace();
+    let name = parts.next()?;
+
+    if parts.next() == Some("COUNT") {
+        Some((name, parts.nth(1)?.parse().ok()?))
+    } else {
+        None
+    }
+}
+
+/// The smallest byte string that is greater than every string with the given prefix.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut result = prefix.to_vec();
+
+    while let Some(last) = result.pop() {
+        if last < u8::MAX {
+            result.push(last + 1);
+            return Some(result);
+        }
+    }
+
+    None
+}
+
+pub fn make_index_key(data: &[u8], case_sensitivity: CaseSensitivity) -> Result<Vec<u8>, Error> {
+    let mut key = Vec::with_capacity(data.len());
+
+    if case_sensitivity == CaseSensitivity::Insensitive {
+        let as_string = std::str::from_utf8(data)?;
+        let lowercase = as_string.to_lowercase();
+
+        key.extend(lowercase.as_bytes());
     } else {
         key.extend_from_slice(data);
     }
 
-    Ok(key)
-}
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        table::Writeable,
+        value::{Blob, DecayScore, Range32, Ranges32, Set32},
+    };
+    use super::*;
+
+    struct Observation {
+        id: u64,
+        value: String,
+        timestamp: u32,
+    }
+
+    impl Observation {
+        fn new(id: u64, value: &str, timestamp: u32) -> Self {
+            Self {
+                id,
+                value: value.to_string(),
+                timestamp,
+            }
+        }
+    }
+
+    fn observations() -> Vec<Observation> {
+        vec![
+            Observation::new(1, "foo", 101),
+            Observation::new(1, "bar", 1),
+            Observation::new(1, "foo", 23),
+            Observation::new(2, "FOO", 23),
+            Observation::new(1, "qux", 50),
+            Observation::new(1, "bar", 1),
+            Observation::new(1, "qux", 0),
+            Observation::new(2, "abc", 23),
+        ]
+    }
+
+    #[test]
+    fn get_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+    }
+
+    #[test]
+    fn byte_array_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32, [u8; 16]> = Hkvdb::new(dir, false).unwrap();
+
+        let first = [1; 16];
+        let second = [0xff; 16];
+
+        db.put(first, "foo", 101).unwrap();
+        db.put(first, "foo", 23).unwrap();
+        db.put(first, "bar", 1).unwrap();
+        db.put(second, "foo", 23).unwrap();
+
+        let expected = vec![
+            ("foo".to_string(), Set32::new(&[23, 101])),
+            ("bar".to_string(), Set32::new(&[1])),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(first).unwrap(), expected);
+        assert_eq!(db.get([0; 16]).unwrap(), HashMap::new());
+        assert_eq!(db.get_counts().unwrap(), (2, 3));
+        assert_eq!(
+            db.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![
+                (first, "bar".to_string(), Set32::new(&[1])),
+                (first, "foo".to_string(), Set32::new(&[23, 101])),
+                (second, "foo".to_string(), Set32::new(&[23])),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.key_count(1).unwrap(), 3);
+        assert_eq!(db.key_count(2).unwrap(), 2);
+        assert_eq!(db.key_count(3).unwrap(), 0);
+    }
+
+    #[test]
+    fn size_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert_eq!(db.size_on_disk().unwrap(), 0);
+
+        db.put_batch(
+            observations()
+                .iter()
+                .map(|observation| (observation.id, &observation.value, observation.timestamp)),
+        )
+        .unwrap();
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        assert!(db.size_on_disk().unwrap() > 0);
+    }
+
+    #[test]
+    fn builder() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder()
+            .block_cache_bytes(1024 * 1024)
+            .enable_statistics(true)
+            .normalize_keys(CaseSensitivity::Insensitive)
+            .auto_index(CaseSensitivity::Insensitive)
+            .open(dir)
+            .unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.merge_value(3, "Foo", (1, 2).into()).unwrap();
+
+        assert!(db.statistics().is_some());
+        assert_eq!(db.get_one(2, "foo").unwrap(), Some((23, 23).into()));
+        assert_eq!(db.search_ci("FOO").unwrap(), vec![1, 2, 3]);
+        assert_eq!(db.search_ci("qux").unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        db.clear().unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        db.clear().unwrap();
+
+        assert_eq!(db.get_counts().unwrap(), (0, 0));
+        assert!(db.get(1).unwrap().is_empty());
+        assert!(db.search("foo").unwrap().is_empty());
+
+        db.put(3, "foo", 1).unwrap();
+
+        assert_eq!(db.get_counts().unwrap(), (1, 1));
+    }
+
+    #[test]
+    fn clear_index_watermark() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(10, "bar", 1).unwrap();
+        db.make_index_incremental(0, CaseSensitivity::Sensitive)
+            .unwrap();
+        assert_eq!(db.index_watermark().unwrap(), Some(11));
+
+        db.clear().unwrap();
+        assert_eq!(db.index_watermark().unwrap(), None);
+
+        db.put(3, "foo", 1).unwrap();
+
+        let watermark = db.index_watermark().unwrap().unwrap_or(0);
+        db.make_index_incremental(watermark, CaseSensitivity::Sensitive)
+            .unwrap();
+
+        assert_eq!(db.search("foo").unwrap(), vec![3]);
+        assert_eq!(db.index_watermark().unwrap(), Some(4));
+    }
+
+    #[test]
+    fn compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder()
+            .compression(DBCompressionType::Zstd)
+            .compression_level(19)
+            .open(dir)
+            .unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+    }
+
+    #[test]
+    fn value_type_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+            db.put(1, "foo", 1).unwrap();
+        }
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+            assert_eq!(db.get_one(1, "foo").unwrap(), Some((1, 1).into()));
+        }
+
+        let result: Result<Hkvdb<Writeable, Set32>, _> = Hkvdb::new(&dir, false);
+
+        match result {
+            Err(Error::ValueTypeMismatch { expected, found }) => {
+                assert_eq!(expected, "Set32");
+                assert_eq!(found, "Range32");
+            }
+            _ => panic!("Expected a value type mismatch"),
+        }
+    }
+
+    #[test]
+    fn value_type_parameter_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let _db: Hkvdb<Writeable, Ranges32<10>> = Hkvdb::new(&dir, false).unwrap();
+        }
+
+        let result: Result<Hkvdb<Writeable, Ranges32<0>>, _> = Hkvdb::new(&dir, false);
+
+        match result {
+            Err(Error::ValueTypeMismatch { expected, found }) => {
+                assert_eq!(expected, "Ranges32/0");
+                assert_eq!(found, "Ranges32/10");
+            }
+            _ => panic!("Expected a value type mismatch"),
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let _db: Hkvdb<Writeable, DecayScore<10>> = Hkvdb::new(&dir, false).unwrap();
+        }
+
+        let result: Result<Hkvdb<Writeable, DecayScore<60>>, _> = Hkvdb::new(&dir, false);
+
+        match result {
+            Err(Error::ValueTypeMismatch { expected, found }) => {
+                assert_eq!(expected, "DecayScore/60");
+                assert_eq!(found, "DecayScore/10");
+            }
+            _ => panic!("Expected a value type mismatch"),
+        }
+    }
+
+    #[test]
+    fn flush_wal() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+            for observation in observations() {
+                db.put(observation.id, &observation.value, observation.timestamp)
+                    .unwrap();
+            }
+
+            db.flush_wal(true).unwrap();
+        }
+
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+    }
+
+    #[test]
+    fn bloom_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> =
+            Hkvdb::builder().bloom_bits_per_key(10.0).open(dir).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_one(2, "FOO").unwrap(), Some((23, 23).into()));
+        assert_eq!(db.get_one(2, "foo").unwrap(), None);
+        assert_eq!(db.get(1).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn first_and_last_observed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.first_observed(1, "foo").unwrap(), Some(23));
+        assert_eq!(db.last_observed(1, "foo").unwrap(), Some(101));
+        assert_eq!(db.first_observed(1, "qux").unwrap(), Some(0));
+        assert_eq!(db.last_observed(2, "FOO").unwrap(), Some(23));
+        assert_eq!(db.first_observed(2, "foo").unwrap(), None);
+        assert_eq!(db.last_observed(3, "foo").unwrap(), None);
+    }
+
+    #[test]
+    fn expire_before() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder().expire_before(50).open(dir).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        // The filter isn't applied to merge operands, so the first compaction only combines them.
+        for _ in 0..2 {
+            db.db
+                .compact_range_cf::<&[u8], &[u8]>(db.by_id_cf(), None, None);
+        }
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_one(1, "qux").unwrap(), Some((0, 50).into()));
+        assert_eq!(db.get_one(1, "bar").unwrap(), None);
+        assert!(db.get(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn expire_before_set32() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::builder().expire_before(50).open(dir).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        for _ in 0..2 {
+            db.db
+                .compact_range_cf::<&[u8], &[u8]>(db.by_id_cf(), None, None);
+        }
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(Set32::new(&[101])));
+        assert_eq!(db.get_one(1, "qux").unwrap(), Some(Set32::new(&[50])));
+        assert_eq!(db.get_one(1, "bar").unwrap(), None);
+        assert!(db.get(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn expire_before_set32_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::builder().expire_before(50).open(dir).unwrap();
+
+        db.put(1, "foo", 10).unwrap();
+        db.put(1, "foo", 20).unwrap();
+        db.put(1, "bar", 60).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), None);
+        assert_eq!(db.get(1).unwrap().len(), 1);
+        assert_eq!(
+            db.get_sorted(1).unwrap(),
+            vec![("bar".to_string(), Set32::new(&[60]))]
+        );
+    }
+
+    #[test]
+    fn two_level_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> =
+            Hkvdb::builder().two_level_index(true).open(dir).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_one(2, "foo").unwrap(), None);
+        assert_eq!(db.get(1).unwrap().len(), 3);
+        assert_eq!(db.iter().count(), 5);
+    }
+
+    #[test]
+    fn filter_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(3, "baz", 200).unwrap();
+
+        let overlaps = |first: u32, last: u32| {
+            move |range: &Range32| range.first() <= last && range.last() >= first
+        };
+
+        assert_eq!(db.filter_ids(overlaps(20, 30)).unwrap(), vec![1, 2]);
+        assert_eq!(db.filter_ids(overlaps(40, 60)).unwrap(), vec![1]);
+        assert_eq!(db.filter_ids(overlaps(150, 250)).unwrap(), vec![3]);
+        assert!(db.filter_ids(overlaps(102, 199)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn resource_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder()
+            .max_open_files(16)
+            .write_buffer_size(1 << 16)
+            .open(dir)
+            .unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+    }
+
+    #[test]
+    fn verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert!(db.verify().unwrap().is_empty());
+
+        db.db
+            .put_cf(db.by_id_cf(), KeyCodec::encode(&3u64, b"bad"), [0, 1, 2])
+            .unwrap();
+
+        assert_eq!(db.verify().unwrap(), vec![(3, b"bad".to_vec())]);
+    }
+
+    #[test]
+    fn index_last_seen() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> =
+            Hkvdb::builder().index_last_seen(true).open(dir).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(3, "foo", 60).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(
+            db.search_with_last_seen("foo").unwrap(),
+            (vec![1, 3], Some(101))
+        );
+        assert_eq!(
+            db.search_with_last_seen("FOO").unwrap(),
+            (vec![2], Some(23))
+        );
+        assert_eq!(db.search_with_last_seen("xyz").unwrap(), (vec![], None));
+
+        db.put_indexed(4, "foo", (200, 250).into(), CaseSensitivity::Sensitive)
+            .unwrap();
+        db.put(5, "bar", 10).unwrap();
+        db.index_id(5, CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(
+            db.search_with_last_seen("foo").unwrap(),
+            (vec![1, 3, 4], Some(250))
+        );
+        assert_eq!(
+            db.search_with_last_seen("bar").unwrap(),
+            (vec![1, 5], Some(10))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert!(matches!(
+            db.search_with_last_seen("foo"),
+            Err(Error::MissingColumnFamily(_))
+        ));
+    }
+
+    #[test]
+    fn without_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder().with_index(false).open(&dir).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+        assert!(db.size_on_disk().is_ok());
+        assert!(matches!(db.search("foo"), Err(Error::IndexDisabled)));
+        assert!(matches!(
+            db.make_index(CaseSensitivity::Sensitive),
+            Err(Error::IndexDisabled)
+        ));
+        assert!(matches!(
+            db.iter_index().next(),
+            Some(Err(Error::IndexDisabled))
+        ));
+
+        db.clear().unwrap();
+        assert_eq!(db.get_counts().unwrap(), (0, 0));
+        db.close().unwrap();
+
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder().with_index(false).open(&dir).unwrap();
+
+        assert!(db.get(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn metrics() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct RecordingSink {
+            gets: AtomicUsize,
+            puts: AtomicUsize,
+            merges: AtomicUsize,
+        }
+
+        impl MetricsSink for RecordingSink {
+            fn on_get(&self, _duration: Duration) {
+                self.gets.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_put(&self, _duration: Duration) {
+                self.puts.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_merge(&self, _duration: Duration) {
+                self.merges.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let sink = Arc::new(RecordingSink::default());
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> =
+            Hkvdb::builder().metrics(sink.clone()).open(dir).unwrap();
+
+        db.put(1, "foo", 1).unwrap();
+        db.put(1, "bar", 2).unwrap();
+        db.put_batch(vec![(2, "foo", 3), (2, "bar", 4)]).unwrap();
+        db.merge_value(1, "foo", (0, 10).into()).unwrap();
+        db.get_one(1, "foo").unwrap();
+        db.get(1).unwrap();
+        db.get(2).unwrap();
+
+        assert_eq!(sink.puts.load(Ordering::SeqCst), 3);
+        assert_eq!(sink.merges.load(Ordering::SeqCst), 1);
+        assert_eq!(sink.gets.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn error_observer() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct RecordingObserver {
+            errors: Mutex<Vec<String>>,
+        }
+
+        impl ErrorObserver for RecordingObserver {
+            fn on_merge_error(&self, column_family: &str, _error: &Error) {
+                self.errors.lock().unwrap().push(column_family.to_string());
+            }
+        }
+
+        let observer = Arc::new(RecordingObserver::default());
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder()
+            .error_observer(observer.clone())
+            .open(dir)
+            .unwrap();
+
+        db.put(1, "foo", 1).unwrap();
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(1.into()));
+        assert!(observer.errors.lock().unwrap().is_empty());
+
+        db.db
+            .put_cf(db.by_id_cf(), KeyCodec::encode(&1, b"bar"), [0, 1, 2])
+            .unwrap();
+        db.put(1, "bar", 10).unwrap();
+
+        assert_eq!(db.get_one(1, "bar").unwrap(), Some(10.into()));
+        assert_eq!(*observer.errors.lock().unwrap(), vec!["by_id"]);
+
+        db.db
+            .put_cf(db.index_cf().unwrap(), b"bar", [0, 1, 2])
+            .unwrap();
+        db.index_id(1, CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(db.search("bar").unwrap(), vec![1]);
+        assert_eq!(*observer.errors.lock().unwrap(), vec!["by_id", "index"]);
+    }
+
+    #[test]
+    fn raw_db() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        let cf = db.by_id_cf_handle().unwrap();
+        let count = db
+            .raw_db()
+            .property_int_value_cf(cf, "rocksdb.estimate-num-keys")
+            .unwrap();
+
+        assert!(count.is_some());
+        assert!(db.index_cf_handle().is_ok());
+        assert!(matches!(
+            db.cf_handle("missing"),
+            Err(Error::MissingColumnFamily(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn ingest_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        let sst_dir = tempfile::tempdir().unwrap();
+        let sst_path = sst_dir.path().join("data.sst");
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put(1, "foo", 1000).unwrap();
+
+        let mut builder = db.sst_builder(&sst_path).unwrap();
+        builder.put(1, "bar", 1).unwrap();
+        builder.put(1, "foo", (23, 101)).unwrap();
+        builder.put(1, "qux", (0, 50)).unwrap();
+        builder.put(2, "FOO", 23).unwrap();
+        builder.put(2, "abc", 23).unwrap();
+        builder.finish().unwrap();
+
+        db.ingest_sorted(&sst_path).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_one(2, "abc").unwrap(), Some((23, 23).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+
+        db.put(1, "foo", 1000).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 1000).into()));
+    }
+
+    #[test]
+    fn close() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        db.close().unwrap();
+
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+        assert_eq!(db.search("foo").unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn close_optional_column_families() {
+        for (with_index, index_last_seen) in [(false, false), (true, true)] {
+            let dir = tempfile::tempdir().unwrap();
+            let builder = || {
+                Hkvdb::<Writeable, Range32>::builder()
+                    .with_index(with_index)
+                    .index_last_seen(index_last_seen)
+            };
+            let db = builder().open(&dir).unwrap();
+
+            for observation in observations() {
+                db.put(observation.id, &observation.value, observation.timestamp)
+                    .unwrap();
+            }
+
+            db.close().unwrap();
+
+            let db = builder().open(&dir).unwrap();
+
+            assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+            assert_eq!(db.get_counts().unwrap(), (2, 5));
+        }
+    }
+
+    #[test]
+    fn get_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_one(2, "FOO").unwrap(), Some((23, 23).into()));
+        assert_eq!(db.get_one(2, "foo").unwrap(), None);
+        assert_eq!(db.get_one(3, "foo").unwrap(), None);
+    }
+
+    #[test]
+    fn get_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(1, "Zed", 1).unwrap();
+        db.put(1, "abc", 1).unwrap();
+
+        let keys = db
+            .get_sorted(1)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+
+        assert_eq!(keys, vec!["Zed", "abc", "bar", "foo", "qux"]);
+        assert_eq!(
+            db.get_sorted(2).unwrap(),
+            vec![
+                ("FOO".to_string(), (23, 23).into()),
+                ("abc".to_string(), (23, 23).into())
+            ]
+        );
+        assert!(db.get_sorted(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn get_opt() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.get_opt(2).unwrap(), Some(db.get(2).unwrap()));
+        assert_eq!(db.get_opt(2).unwrap().unwrap().len(), 2);
+        assert_eq!(db.get_opt(0).unwrap(), None);
+        assert_eq!(db.get_opt(3).unwrap(), None);
+    }
+
+    #[test]
+    fn get_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_batch(vec![
+            (1, "rob", 1),
+            (1, "roberta", 2),
+            (1, "ralph", 3),
+            (1, "quinn", 4),
+            (1, "sam", 5),
+            (2, "rachel", 6),
+        ])
+        .unwrap();
+
+        let expected = vec![
+            ("rob".to_string(), (1, 1).into()),
+            ("roberta".to_string(), (2, 2).into()),
+            ("ralph".to_string(), (3, 3).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get_prefix(1, "r").unwrap(), expected);
+        assert_eq!(db.get_prefix(1, "rob").unwrap().len(), 2);
+        assert_eq!(db.get_prefix(1, "").unwrap().len(), 5);
+        assert!(db.get_prefix(1, "t").unwrap().is_empty());
+        assert!(db.get_prefix(3, "r").unwrap().is_empty());
+    }
+
+    #[test]
+    fn case_insensitive_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> =
+            Hkvdb::new_with_key_case(dir, false, CaseSensitivity::Insensitive).unwrap();
+
+        db.put(1, "FOO", 10).unwrap();
+        db.put(1, "foo", 20).unwrap();
+        db.put_batch(vec![(1, "Foo", 5), (1, "bar", 1)]).unwrap();
+
+        let expected = vec![
+            ("foo".to_string(), (5, 20).into()),
+            ("bar".to_string(), (1, 1).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).unwrap(), expected);
+        assert_eq!(db.get_one(1, "fOO").unwrap(), Some((5, 20).into()));
+        assert_eq!(db.get_counts().unwrap(), (1, 2));
+    }
+
+    #[test]
+    fn open_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+
+        let result = Hkvdb::<Writeable, Range32>::open_existing(&path, false);
+        assert!(matches!(result, Err(Error::Db(_))));
+        assert!(!path.exists());
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&path, false).unwrap();
+            db.put(1, "foo", 10).unwrap();
+        }
+
+        let db = Hkvdb::<Writeable, Range32>::open_existing(&path, false).unwrap();
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(10.into()));
+    }
+
+    #[test]
+    fn wal_recovery_mode() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+            for observation in observations() {
+                db.put(observation.id, &observation.value, observation.timestamp)
+                    .unwrap();
+            }
+            db.db.flush_cf(db.by_id_cf()).unwrap();
+        }
+
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder()
+            .wal_recovery_mode(DBRecoveryMode::PointInTime)
+            .open(&dir)
+            .unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+    }
+
+    #[test]
+    fn level_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert!(db.level_stats().unwrap().is_empty());
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+            db.db.flush_cf(db.by_id_cf()).unwrap();
+        }
+
+        let stats = db.level_stats().unwrap();
+
+        assert!(stats.iter().any(|stat| stat.num_files > 0));
+        assert!(stats.iter().all(|stat| stat.size_bytes > 0));
+        assert!(stats.windows(2).all(|pair| pair[0].level < pair[1].level));
+    }
+
+    #[test]
+    fn estimate_keys_in_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for id in 0..10 {
+            db.put(id, "foo", 1).unwrap();
+            db.put(id, "bar", 1).unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        for id in 100..110 {
+            db.put(id, "foo", 1).unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        assert!(db.estimate_keys_in_range(0, 10).unwrap() > 0);
+        assert!(db.estimate_keys_in_range(5, 105).unwrap() >= 20);
+        assert_eq!(db.estimate_keys_in_range(20, 50).unwrap(), 0);
+        assert_eq!(db.estimate_keys_in_range(200, 300).unwrap(), 0);
+    }
+
+    #[test]
+    fn backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_dir = tempfile::tempdir().unwrap();
+        let restored_dir = tempfile::tempdir().unwrap();
+
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        db.backup(&backup_dir).unwrap();
+        db.put(3, "foo", 1).unwrap();
+        db.backup(&backup_dir).unwrap();
+
+        restore_from_backup(&backup_dir, &restored_dir).unwrap();
+
+        let restored: Hkvdb<Writeable, Range32> = Hkvdb::new(&restored_dir, false).unwrap();
+
+        assert_eq!(
+            restored.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            db.iter().collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new_with_ttl(&dir, 60 * 60, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+        assert_eq!(db.get_counts().unwrap(), (2, 5));
+
+        drop(db);
+
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new_with_ttl(&dir, 60 * 60, false).unwrap();
+
+        assert_eq!(db.get_one(1, "qux").unwrap(), Some((0, 50).into()));
+    }
+
+    #[test]
+    fn ttl_excludes_meta() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new_with_ttl(&dir, 1, false).unwrap();
+            db.put(1, "foo", 1).unwrap();
+        }
+
+        // Timestamps have a resolution of a second, so this is the shortest reliable wait.
+        std::thread::sleep(Duration::from_secs(2));
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new_with_ttl(&dir, 1, false).unwrap();
+
+            for name in ["by_id", "meta"] {
+                db.db
+                    .compact_range_cf::<&[u8], &[u8]>(db.cf_handle(name).unwrap(), None, None);
+            }
+
+            assert_eq!(db.get_one(1, "foo").unwrap(), None);
+        }
+
+        let result: Result<Hkvdb<Writeable, Set32>, _> = Hkvdb::new_with_ttl(&dir, 1, false);
+
+        assert!(matches!(result, Err(Error::ValueTypeMismatch { .. })));
+    }
+
+    #[test]
+    fn invalid_utf8_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put(1, "foo", 1).unwrap();
+        db.put_raw(1, &[0x66, 0xff, 0x6f], 2).unwrap();
+
+        match db.get(1) {
+            Err(Error::InvalidUtf8Key { bytes, .. }) => assert_eq!(bytes, vec![0x66, 0xff, 0x6f]),
+            other => panic!("Expected invalid UTF-8 key error, got {:?}", other),
+        }
+
+        assert!(matches!(
+            db.iter().collect::<Result<Vec<_>, _>>(),
+            Err(Error::InvalidUtf8Key { .. })
+        ));
+    }
+
+    #[test]
+    fn get_raw_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        // Bytes that aren't a valid range are still returned.
+        db.db
+            .put_cf(db.by_id_cf(), KeyCodec::encode(&2, b"bad"), [1, 2, 3])
+            .unwrap();
+
+        let values = db.get_raw_bytes(1).unwrap();
+
+        assert_eq!(values.len(), 3);
+        assert_eq!(
+            values.get(b"foo".as_slice()),
+            Some(&Vec::from(Range32::new(23, 101)))
+        );
+
+        let values = db.get_raw_bytes(2).unwrap();
+
+        assert_eq!(values.get(b"bad".as_slice()), Some(&vec![1, 2, 3]));
+        assert!(db.get(2).is_err());
+    }
+
+    #[test]
+    fn id_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert_eq!(db.id_bounds().unwrap(), None);
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.id_bounds().unwrap(), Some((1, 2)));
+
+        db.put(u64::MAX, "foo", 1).unwrap();
+
+        assert_eq!(db.id_bounds().unwrap(), Some((1, u64::MAX)));
+    }
+
+    #[test]
+    fn prefetch_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, true).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        let baseline = db.statistics_parsed().unwrap();
+
+        db.prefetch_ids(&[1, 2, 3]).unwrap();
+
+        let delta = db.statistics_delta_since(&baseline).unwrap();
+
+        assert!(delta["rocksdb.block.cache.add"] > 0.0);
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((23, 101).into()));
+    }
+
+    #[test]
+    fn compact_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(u64::MAX, "foo", 1).unwrap();
+
+        db.db
+            .delete_cf(db.by_id_cf(), KeyCodec::encode(&1, b"bar"))
+            .unwrap();
+        db.db
+            .delete_cf(db.by_id_cf(), KeyCodec::encode(&u64::MAX, b"foo"))
+            .unwrap();
+
+        db.compact_id(1).unwrap();
+        db.compact_id(u64::MAX).unwrap();
+
+        let expected = vec![
+            ("foo".to_string(), (23, 101).into()),
+            ("qux".to_string(), (0, 50).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).unwrap(), expected);
+        assert_eq!(db.get(2).unwrap().len(), 2);
+        assert_eq!(db.get(u64::MAX).unwrap(), HashMap::new());
+    }
+
+    #[test]
+    fn prefix_successor() {
+        assert_eq!(super::prefix_successor(&[0, 1]), Some(vec![0, 2]));
+        assert_eq!(super::prefix_successor(&[0, 255]), Some(vec![1]));
+        assert_eq!(super::prefix_successor(&[255, 255]), None);
+        assert_eq!(
+            super::prefix_successor(&KeyCodec::prefix(&1)),
+            Some(KeyCodec::prefix(&2))
+        );
+    }
+
+    #[test]
+    fn cache_hit_ratio() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, true).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+
+        for _ in 0..10 {
+            db.get(1).unwrap();
+            db.get(2).unwrap();
+        }
+
+        let ratio = db.cache_hit_ratio().unwrap();
+
+        assert!((0.0..=1.0).contains(&ratio));
+    }
+
+    #[test]
+    fn statistics_delta_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, true).unwrap();
+
+        db.put(1, "foo", 1).unwrap();
+        db.get_one(1, "foo").unwrap();
+
+        let baseline = db.statistics_parsed().unwrap();
+
+        assert!(baseline["rocksdb.number.keys.written"] >= 1.0);
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        for _ in 0..3 {
+            db.get_one(1, "foo").unwrap();
+        }
+
+        let delta = db.statistics_delta_since(&baseline).unwrap();
+
+        assert_eq!(delta["rocksdb.number.keys.written"], 8.0);
+        assert_eq!(delta["rocksdb.number.keys.read"], 3.0);
+        assert!(delta.values().all(|value| *value >= 0.0));
+    }
+
+    #[test]
+    fn statistics_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert_eq!(db.statistics_parsed(), None);
+        assert_eq!(db.statistics_delta_since(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn cache_hit_ratio_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put(1, "foo", 1).unwrap();
+        db.get(1).unwrap();
+
+        assert_eq!(db.cache_hit_ratio(), None);
+    }
+
+    #[test]
+    fn parse_ticker() {
+        let statistics = "rocksdb.block.cache.miss COUNT : 5\nrocksdb.block.cache.hit COUNT : 12\nrocksdb.db.get.micros P50 : 1.000000 P95 : 2.000000\n";
+
+        assert_eq!(
+            super::parse_ticker(statistics, "rocksdb.block.cache.hit"),
+            Some(12)
+        );
+        assert_eq!(
+            super::parse_ticker(statistics, "rocksdb.block.cache.miss"),
+            Some(5)
+        );
+        assert_eq!(
+            super::parse_ticker(statistics, "rocksdb.db.get.micros"),
+            None
+        );
+        assert_eq!(super::parse_ticker(statistics, "rocksdb.block.cache"), None);
+    }
+
+    #[test]
+    fn put_raw_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_raw_batch(observations().iter().map(|observation| {
+            (
+                observation.id,
+                observation.value.as_bytes(),
+                observation.timestamp,
+            )
+        }))
+        .unwrap();
+
+        let expected = vec![
+            ("foo".to_string(), (23, 101).into()),
+            ("bar".to_string(), (1, 1).into()),
+            ("qux".to_string(), (0, 50).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).unwrap(), expected);
+    }
+
+    #[test]
+    fn put_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_batch(
+            observations()
+                .iter()
+                .map(|observation| (observation.id, &observation.value, observation.timestamp)),
+        )
+        .unwrap();
+
+        let expected = vec![
+            ("foo".to_string(), (23, 101).into()),
+            ("bar".to_string(), (1, 1).into()),
+            ("qux".to_string(), (0, 50).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).unwrap(), expected);
+    }
+
+    #[test]
+    fn put_batch_chunked() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_batch_chunked(
+            (0..1000).map(|i| (i % 10, format!("{}", i % 7), i as u32)),
+            3,
+        )
+        .unwrap();
+
+        assert_eq!(db.get_counts().unwrap(), (10, 70));
+
+        let values = db.get(3).unwrap();
+
+        assert_eq!(values.len(), 7);
+        assert_eq!(
+            values.get("0").unwrap().values(),
+            (0..1000)
+                .filter(|i| i % 10 == 3 && i % 7 == 0)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn blob_last_writer_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Blob> = Hkvdb::new(dir, false).unwrap();
+
+        db.put(1, "foo", Blob::new(b"first".to_vec())).unwrap();
+        db.put(1, "foo", Blob::new(b"second".to_vec())).unwrap();
+        db.put(1, "bar", Blob::new(b"other".to_vec())).unwrap();
+
+        assert_eq!(
+            db.get_one(1, "foo").unwrap(),
+            Some(Blob::new(b"second".to_vec()))
+        );
+
+        db.db.flush_cf(db.by_id_cf()).unwrap();
+        db.put(1, "foo", Blob::new(b"third".to_vec())).unwrap();
+        db.db
+            .compact_range_cf::<&[u8], &[u8]>(db.by_id_cf(), None, None);
+
+        assert_eq!(
+            db.get_one(1, "foo").unwrap(),
+            Some(Blob::new(b"third".to_vec()))
+        );
+        assert_eq!(
+            db.get_one(1, "bar").unwrap(),
+            Some(Blob::new(b"other".to_vec()))
+        );
+    }
+
+    #[test]
+    fn write_mode_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::builder()
+            .write_mode(WriteMode::Overwrite)
+            .open(dir)
+            .unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(23.into()));
+        assert_eq!(db.get_one(1, "qux").unwrap(), Some(0.into()));
+
+        db.put_batch(vec![(1, "foo", 5), (1, "foo", 7)]).unwrap();
+        db.merge_value(1, "bar", (10, 20).into()).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(7.into()));
+        assert_eq!(db.get_one(1, "bar").unwrap(), Some((10, 20).into()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::builder()
+            .write_mode(WriteMode::Overwrite)
+            .open(dir)
+            .unwrap();
+
+        db.put(1, "foo", 1).unwrap();
+        db.merge_value(1, "foo", Set32::new(&[])).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), None);
+    }
+
+    #[test]
+    fn par_for_each() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for id in 0..100 {
+            for timestamp in 0..(id % 7) {
+                db.put(id * 1000, &format!("key-{}", timestamp), timestamp as u32)
+                    .unwrap();
+            }
+        }
+        db.put(u64::MAX, "foo", 1).unwrap();
+
+        let serial = db
+            .iter()
+            .map(|result| result.unwrap().2.values().len() as u64)
+            .sum::<u64>();
+
+        let rows = AtomicU64::new(0);
+        let observations = AtomicU64::new(0);
+
+        db.par_for_each(|_, _, value| {
+            rows.fetch_add(1, Ordering::SeqCst);
+            observations.fetch_add(value.values().len() as u64, Ordering::SeqCst);
+        })
+        .unwrap();
+
+        assert_eq!(observations.load(Ordering::SeqCst), serial);
+        assert_eq!(rows.load(Ordering::SeqCst), db.get_counts().unwrap().1);
+    }
+
+    #[test]
+    fn split_id_range() {
+        assert_eq!(super::split_id_range(0, 9, 3), vec![(0, 2), (3, 5), (6, 9)]);
+        assert_eq!(super::split_id_range(5, 6, 4), vec![(5, 5), (6, 6)]);
+        assert_eq!(super::split_id_range(7, 7, 0), vec![(7, 7)]);
+        assert_eq!(
+            super::split_id_range(0, u64::MAX, 2),
+            vec![(0, u64::MAX / 2), (u64::MAX / 2 + 1, u64::MAX)]
+        );
+    }
+
+    #[test]
+    fn put_ranges_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put(1, "foo", 50).unwrap();
+        db.put_ranges_batch(vec![
+            (1, "foo".to_string(), Range32::new(10, 20)),
+            (1, "bar".to_string(), Range32::new(1, 2)),
+            (1, "foo".to_string(), Range32::new(15, 30)),
+            (2, "foo".to_string(), Range32::new(5, 5)),
+        ])
+        .unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(Range32::new(10, 50)));
+        assert_eq!(db.get_one(1, "bar").unwrap(), Some(Range32::new(1, 2)));
+        assert_eq!(db.get_one(2, "foo").unwrap(), Some(Range32::new(5, 5)));
+        assert_eq!(db.get_counts().unwrap(), (2, 3));
+    }
+
+    #[test]
+    fn put_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert_eq!(
+            db.put_and_get(1, "foo", (10, 20).into()).unwrap(),
+            Range32::new(10, 20)
+        );
+        assert_eq!(
+            db.put_and_get(1, "foo", 5.into()).unwrap(),
+            Range32::new(5, 20)
+        );
+        assert_eq!(
+            db.put_and_get(1, "foo", 30.into()).unwrap(),
+            Range32::new(5, 30)
+        );
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some(Range32::new(5, 30)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        assert_eq!(
+            db.put_and_get(1, "foo", Set32::new(&[])).unwrap(),
+            Set32::new(&[])
+        );
+        assert_eq!(db.get_one(1, "foo").unwrap(), None);
+        db.put(1, "foo", 3).unwrap();
+        assert_eq!(
+            db.put_and_get(1, "foo", Set32::new(&[])).unwrap(),
+            Set32::new(&[3])
+        );
+    }
+
+    #[test]
+    fn put_if_absent() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert!(db.put_if_absent(1, "foo", (10, 20).into()).unwrap());
+        assert!(!db.put_if_absent(1, "foo", (0, 30).into()).unwrap());
+        assert!(db.put_if_absent(2, "foo", (0, 30).into()).unwrap());
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((10, 20).into()));
+        assert_eq!(db.get_one(2, "foo").unwrap(), Some((0, 30).into()));
+
+        db.put(1, "foo", 25).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((10, 25).into()));
+    }
+
+    #[test]
+    fn skip_empty_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put(1, "foo", Set32::new(&[])).unwrap();
+        db.merge_value(1, "bar", Set32::new(&[])).unwrap();
+        db.put_batch(vec![
+            (2, "foo", Set32::new(&[])),
+            (2, "bar", Set32::singleton(1)),
+        ])
+        .unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), None);
+        assert_eq!(db.get_counts().unwrap(), (1, 1));
+
+        assert!(!db.put_if_absent(3, "foo", Set32::new(&[])).unwrap());
+        assert_eq!(db.get_counts().unwrap(), (1, 1));
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::builder()
+            .write_mode(WriteMode::Overwrite)
+            .open(dir)
+            .unwrap();
+
+        db.put_indexed(1, "foo", Set32::singleton(1), CaseSensitivity::Sensitive)
+            .unwrap();
+        db.put_indexed(1, "foo", Set32::new(&[]), CaseSensitivity::Sensitive)
+            .unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), None);
+        assert_eq!(
+            db.get_raw_bytes(1).unwrap(),
+            HashMap::from([(b"foo".to_vec(), vec![])])
+        );
+    }
+
+    #[test]
+    fn merge_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        db.merge_value(1, "foo", Set32::new(&[5, 23, 7, 1000, 3]))
+            .unwrap();
+        db.merge_value(3, "foo", Set32::new(&[1, 2, 3, 4, 5]))
+            .unwrap();
+
+        let values = db.get(1).unwrap();
+
+        assert_eq!(
+            values.get("foo"),
+            Some(&Set32::new(&[3, 5, 7, 23, 101, 1000]))
+        );
+        assert_eq!(values.get("bar"), Some(&Set32::new(&[1])));
+        assert_eq!(
+            db.get(3).unwrap().get("foo"),
+            Some(&Set32::new(&[1, 2, 3, 4, 5]))
+        );
+    }
+
+    #[test]
+    fn iter() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_batch(
+            observations()
+                .iter()
+                .map(|observation| (observation.id, &observation.value, observation.timestamp)),
+        )
+        .unwrap();
+
+        let expected: Vec<(u64, String, Range32)> = vec![
+            (1, "bar".to_string(), (1, 1).into()),
+            (1, "foo".to_string(), (23, 101).into()),
+            (1, "qux".to_string(), (0, 50).into()),
+            (2, "FOO".to_string(), (23, 23).into()),
+            (2, "abc".to_string(), (23, 23).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.iter().collect::<Result<Vec<_>, _>>().unwrap(), expected);
+    }
+
+    #[test]
+    fn iter_grouped() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert_eq!(db.iter_grouped().count(), 0);
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(10, "foo", 1).unwrap();
+
+        let grouped = db.iter_grouped().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(
+            grouped.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![1, 2, 10]
+        );
+
+        for (id, values) in grouped {
+            assert_eq!(values, db.get(id).unwrap());
+        }
+    }
+
+    #[test]
+    fn observe() {
+        let observed_dir = tempfile::tempdir().unwrap();
+        let observed: Hkvdb<Writeable, Set32> = Hkvdb::new(observed_dir, false).unwrap();
+        let put_dir = tempfile::tempdir().unwrap();
+        let put: Hkvdb<Writeable, Set32> = Hkvdb::new(put_dir, false).unwrap();
+
+        for observation in observations() {
+            observed
+                .observe(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+            put.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        for id in [1, 2] {
+            assert_eq!(observed.get(id).unwrap(), put.get(id).unwrap());
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.observe(1, "foo", 10).unwrap();
+        db.observe(1, "foo", 5).unwrap();
+
+        assert_eq!(db.get_one(1, "foo").unwrap(), Some((5, 10).into()));
+    }
+
+    #[test]
+    fn iter_id_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(u64::MAX, "foo", 1).unwrap();
+
+        let ids = |start, end| {
+            db.iter_id_range(start, end)
+                .map(|result| result.map(|(id, _, _)| id))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        assert_eq!(ids(1, 2), vec![1, 1, 1]);
+        assert_eq!(ids(0, 3), vec![1, 1, 1, 2, 2]);
+        assert_eq!(ids(2, u64::MAX), vec![2, 2]);
+        assert_eq!(ids(3, u64::MAX), Vec::<u64>::new());
+        assert_eq!(ids(2, 1), Vec::<u64>::new());
+
+        let rows = db
+            .iter_id_range(1, 2)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            rows,
+            db.iter().take(3).collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn timestamp_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        let expected = vec![
+            ("foo".to_string(), (23, 101).into()),
+            ("bar".to_string(), (1, 1).into()),
+            ("qux".to_string(), (0, 50).into()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).unwrap(), expected);
+    }
+
+    #[test]
+    fn timestamp_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        let expected = vec![
+            ("foo".to_string(), Set32::new(&[23, 101])),
+            ("bar".to_string(), Set32::new(&[1])),
+            ("qux".to_string(), Set32::new(&[0, 50])),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(db.get(1).unwrap(), expected);
+    }
+
+    #[test]
+    fn search() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(db.search("foo").unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn make_index_batched() {
+        let one_at_a_time_dir = tempfile::tempdir().unwrap();
+        let one_at_a_time: Hkvdb<Writeable, Set32> = Hkvdb::new(one_at_a_time_dir, false).unwrap();
+        let batched_dir = tempfile::tempdir().unwrap();
+        let batched: Hkvdb<Writeable, Set32> = Hkvdb::new(batched_dir, false).unwrap();
+
+        let mut batch = vec![];
+        for id in 0..100 {
+            for value in ["foo", "Foo", "bar", "qux"] {
+                if id < 66 || value != "qux" {
+                    batch.push((id, value.to_string(), id as u32));
+                }
+            }
+        }
+
+        one_at_a_time.put_batch(batch.clone()).unwrap();
+        batched.put_batch(batch).unwrap();
+
+        one_at_a_time
+            .make_index_batched(CaseSensitivity::Insensitive, 1)
+            .unwrap();
+        batched
+            .make_index_batched(CaseSensitivity::Insensitive, 7)
+            .unwrap();
+
+        for term in ["foo", "FOO", "bar", "qux", "xyz"] {
+            assert_eq!(
+                batched.search_ci(term).unwrap(),
+                one_at_a_time.search_ci(term).unwrap()
+            );
+        }
+        assert_eq!(batched.search_ci("qux").unwrap().len(), 66);
+    }
+
+    #[test]
+    fn make_index_parallel() {
+        let serial_dir = tempfile::tempdir().unwrap();
+        let serial: Hkvdb<Writeable, Set32> = Hkvdb::new(serial_dir, false).unwrap();
+        let parallel_dir = tempfile::tempdir().unwrap();
+        let parallel: Hkvdb<Writeable, Set32> = Hkvdb::new(parallel_dir, false).unwrap();
+
+        let mut batch = vec![];
+        for id in (0..1000).chain([u64::MAX - 1, u64::MAX]) {
+            let value = if id < 500 { "foo" } else { "bar" };
+            batch.push((id, value, 1));
+            batch.push((id, "qux", 1));
+        }
+
+        serial.put_batch(batch.clone()).unwrap();
+        parallel.put_batch(batch).unwrap();
+
+        serial.make_index(CaseSensitivity::Sensitive).unwrap();
+        parallel
+            .make_index_parallel(CaseSensitivity::Sensitive, 4)
+            .unwrap();
+
+        for term in ["foo", "bar", "qux", "xyz"] {
+            assert_eq!(parallel.search(term).unwrap(), serial.search(term).unwrap());
+        }
+        assert_eq!(parallel.search("qux").unwrap().len(), 1002);
+
+        let empty_dir = tempfile::tempdir().unwrap();
+        let empty: Hkvdb<Writeable, Set32> = Hkvdb::new(empty_dir, false).unwrap();
+
+        empty
+            .make_index_parallel(CaseSensitivity::Sensitive, 4)
+            .unwrap();
+    }
+
+    #[test]
+    fn search_iter() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(u64::MAX, "foo", 1).unwrap();
+        db.put(3, "foo", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        for term in ["foo", "FOO", "bar", "xyz"] {
+            let iter = db.search_iter(term).unwrap();
+
+            assert_eq!(iter.len(), db.search(term).unwrap().len());
+            assert_eq!(iter.collect::<Vec<_>>(), db.search(term).unwrap());
+        }
+
+        assert_eq!(
+            db.search_iter("foo").unwrap().collect::<Vec<_>>(),
+            vec![1, 3, u64::MAX]
+        );
+    }
+
+    #[test]
+    fn search_any_iter() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(u64::MAX, "foo", 1).unwrap();
+        db.put(3, "bar", 1).unwrap();
+        db.put(0, "abc", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        let terms = ["foo", "FOO", "bar", "abc", "xyz"];
+        let mut expected = terms
+            .iter()
+            .flat_map(|term| db.search(term).unwrap())
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+        expected.dedup();
+
+        assert_eq!(
+            db.search_any_iter(&terms).unwrap().collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(expected, vec![0, 1, 2, 3, u64::MAX]);
+        assert_eq!(db.search_any_iter(&[]).unwrap().count(), 0);
+        assert_eq!(db.search_any_iter(&["xyz"]).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn search_many() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(3, "foo", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        let terms = ["foo", "FOO", "bar", "xyz", "abc", ""];
+        let expected = terms
+            .iter()
+            .map(|term| (term.to_string(), db.search(term).unwrap()))
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(db.search_many(&terms).unwrap(), expected);
+        assert_eq!(expected["foo"], vec![1, 3]);
+        assert!(expected["xyz"].is_empty());
+        assert!(db.search_many(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_paged() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for id in 1..=7 {
+            db.put(id * 10, "foo", 1).unwrap();
+        }
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(db.search_paged("foo", 0, 3).unwrap(), vec![10, 20, 30]);
+        assert_eq!(db.search_paged("foo", 3, 3).unwrap(), vec![40, 50, 60]);
+        assert_eq!(db.search_paged("foo", 6, 3).unwrap(), vec![70]);
+        assert!(db.search_paged("foo", 9, 3).unwrap().is_empty());
+        assert!(db.search_paged("foo", usize::MAX, 3).unwrap().is_empty());
+        assert!(db.search_paged("foo", 0, 0).unwrap().is_empty());
+        assert!(db.search_paged("bar", 0, 3).unwrap().is_empty());
+
+        let mut iter = db.search_iter("foo").unwrap();
+
+        assert_eq!(iter.nth(2), Some(30));
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.nth(10), None);
+        assert_eq!(iter.len(), 0);
+    }
+
+    #[test]
+    fn search_with_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(3, "foo", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(db.search_with_count("foo").unwrap(), (2, vec![1, 3]));
+        assert_eq!(db.search_with_count("xyz").unwrap(), (0, vec![]));
+    }
+
+    #[test]
+    fn search_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(3, "foo", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        for term in ["foo", "FOO", "bar", "abc", "xyz"] {
+            assert_eq!(
+                db.search_count(term).unwrap(),
+                db.search(term).unwrap().len()
+            );
+        }
+    }
+
+    #[test]
+    fn search_contains() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for id in [1, 3, 4, 7, 10, 20, 21] {
+            db.put(id, "foo", 1).unwrap();
+        }
+        db.put(2, "bar", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        for id in 0..25 {
+            assert_eq!(
+                db.search_contains("foo", id).unwrap(),
+                db.search("foo").unwrap().contains(&id)
+            );
+        }
+
+        assert!(db.search_contains("bar", 2).unwrap());
+        assert!(!db.search_contains("bar", 1).unwrap());
+        assert!(!db.search_contains("xyz", 1).unwrap());
+    }
+
+    #[test]
+    fn search_intersection_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::super::{
-        table::Writeable,
-        value::{Range32, Set32},
-    };
-    use super::*;
+        for (id, data) in [
+            (1, "foo"),
+            (2, "foo"),
+            (3, "foo"),
+            (5, "foo"),
+            (2, "bar"),
+            (3, "bar"),
+            (4, "bar"),
+            (5, "bar"),
+            (6, "bar"),
+            (7, "qux"),
+        ] {
+            db.put(id, data, 1).unwrap();
+        }
 
-    struct Observation {
-        id: u64,
-        value: String,
-        timestamp: u32,
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(db.search_intersection_count("foo", "bar").unwrap(), 3);
+        assert_eq!(db.search_intersection_count("bar", "foo").unwrap(), 3);
+        assert_eq!(db.search_intersection_count("foo", "foo").unwrap(), 4);
+        assert_eq!(db.search_intersection_count("foo", "qux").unwrap(), 0);
+        assert_eq!(db.search_intersection_count("foo", "xyz").unwrap(), 0);
     }
 
-    impl Observation {
-        fn new(id: u64, value: &str, timestamp: u32) -> Self {
-            Self {
-                id,
-                value: value.to_string(),
-                timestamp,
-            }
+    #[test]
+    fn search_ci() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
         }
+
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
+
+        assert_eq!(db.search_ci("foo").unwrap(), vec![1, 2]);
     }
 
-    fn observations() -> Vec<Observation> {
-        vec![
-            Observation::new(1, "foo", 101),
-            Observation::new(1, "bar", 1),
-            Observation::new(1, "foo", 23),
-            Observation::new(2, "FOO", 23),
-            Observation::new(1, "qux", 50),
-            Observation::new(1, "bar", 1),
-            Observation::new(1, "qux", 0),
-            Observation::new(2, "abc", 23),
-        ]
+    #[test]
+    fn search_either() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+        db.put(3, "Bar", 1).unwrap();
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
+
+        assert_eq!(db.search_either("FOO").unwrap(), vec![2]);
+        assert_eq!(db.search_either("Foo").unwrap(), vec![1, 2]);
+        assert_eq!(db.search_either("Bar").unwrap(), vec![3]);
+        assert_eq!(db.search_either("BAR").unwrap(), vec![1, 3]);
+        assert!(db.search_either("xyz").unwrap().is_empty());
     }
 
     #[test]
-    fn get_counts() {
+    fn search_ci_prenormalized() {
         let dir = tempfile::tempdir().unwrap();
-        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
         for observation in observations() {
             db.put(observation.id, &observation.value, observation.timestamp)
                 .unwrap();
         }
 
-        assert_eq!(db.get_counts().unwrap(), (2, 5));
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
+
+        for term in ["foo", "bar", "abc", "xyz"] {
+            assert_eq!(
+                db.search_ci_prenormalized(term).unwrap(),
+                db.search_ci(term).unwrap()
+            );
+        }
+        assert_eq!(db.search_ci_prenormalized("foo").unwrap(), vec![1, 2]);
     }
 
     #[test]
-    fn put_raw_batch() {
+    fn estimate_index_work() {
         let dir = tempfile::tempdir().unwrap();
-        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
-        db.put_raw_batch(observations().iter().map(|observation| {
-            (
-                observation.id,
-                observation.value.as_bytes(),
-                observation.timestamp,
-            )
-        }))
-        .unwrap();
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
 
-        let expected = vec![
-            ("foo".to_string(), (23, 101).into()),
-            ("bar".to_string(), (1, 1).into()),
-            ("qux".to_string(), (0, 50).into()),
-        ]
-        .into_iter()
-        .collect();
+        db.db
+            .compact_range_cf::<&[u8], &[u8]>(db.by_id_cf(), None, None);
 
-        assert_eq!(db.get(1).unwrap(), expected);
+        assert_eq!(
+            db.estimate_index_work().unwrap(),
+            db.get_counts().unwrap().1
+        );
     }
 
     #[test]
-    fn put_batch() {
+    fn index_term_counts() {
         let dir = tempfile::tempdir().unwrap();
-        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
-        db.put_batch(
-            observations()
-                .iter()
-                .map(|observation| (observation.id, &observation.value, observation.timestamp)),
-        )
-        .unwrap();
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
 
-        let expected = vec![
-            ("foo".to_string(), (23, 101).into()),
-            ("bar".to_string(), (1, 1).into()),
-            ("qux".to_string(), (0, 50).into()),
-        ]
-        .into_iter()
-        .collect();
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
 
-        assert_eq!(db.get(1).unwrap(), expected);
+        let counts = db
+            .index_term_counts()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            counts,
+            vec![
+                ("abc".to_string(), 1),
+                ("bar".to_string(), 1),
+                ("foo".to_string(), 2),
+                ("qux".to_string(), 1)
+            ]
+        );
     }
 
     #[test]
-    fn iter() {
+    fn index_remove() {
         let dir = tempfile::tempdir().unwrap();
-        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
-        db.put_batch(
-            observations()
-                .iter()
-                .map(|observation| (observation.id, &observation.value, observation.timestamp)),
-        )
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
+
+        assert_eq!(db.search_ci("foo").unwrap(), vec![1, 2]);
+
+        db.index_remove("FOO", 1, CaseSensitivity::Insensitive)
+            .unwrap();
+
+        assert_eq!(db.search_ci("foo").unwrap(), vec![2]);
+
+        db.index_remove("foo", 2, CaseSensitivity::Insensitive)
+            .unwrap();
+        db.index_remove("missing", 2, CaseSensitivity::Insensitive)
+            .unwrap();
+
+        assert!(db.search_ci("foo").unwrap().is_empty());
+        assert!(db
+            .db
+            .get_pinned_cf(db.index_cf().unwrap(), "foo")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn index_term_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
+
+        let estimate = db.index_term_count().unwrap();
+
+        assert!((1..=5).contains(&estimate));
+
+        db.db
+            .compact_range_cf::<&[u8], &[u8]>(db.index_cf().unwrap(), None, None);
+
+        assert_eq!(db.index_term_count().unwrap(), 4);
+    }
+
+    #[test]
+    fn migrate() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dst_dir = tempfile::tempdir().unwrap();
+        let src: Hkvdb<Writeable, Set32> = Hkvdb::new(src_dir, false).unwrap();
+        let dst: Hkvdb<Writeable, Range32> = Hkvdb::new(dst_dir, false).unwrap();
+
+        for observation in observations() {
+            src.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        super::migrate(&src, &dst, |value: Set32| {
+            Range32::from_timestamps(value).unwrap()
+        })
         .unwrap();
 
-        let expected: Vec<(u64, String, Range32)> = vec![
-            (1, "bar".to_string(), (1, 1).into()),
-            (1, "foo".to_string(), (23, 101).into()),
-            (1, "qux".to_string(), (0, 50).into()),
-            (2, "FOO".to_string(), (23, 23).into()),
-            (2, "abc".to_string(), (23, 23).into()),
-        ]
-        .into_iter()
-        .collect();
+        for id in [1, 2, 3] {
+            let expected = src
+                .get(id)
+                .unwrap()
+                .into_iter()
+                .map(|(data, value)| (data, Range32::from_timestamps(value).unwrap()))
+                .collect::<HashMap<_, _>>();
 
-        assert_eq!(db.iter().collect::<Result<Vec<_>, _>>().unwrap(), expected);
+            assert_eq!(dst.get(id).unwrap(), expected);
+        }
+
+        assert_eq!(dst.get(1).unwrap()["foo"], Range32::new(23, 101));
     }
 
     #[test]
-    fn timestamp_range() {
+    fn verify_index() {
+        for case_sensitivity in [CaseSensitivity::Sensitive, CaseSensitivity::Insensitive] {
+            let dir = tempfile::tempdir().unwrap();
+            let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+            for observation in observations() {
+                db.put(observation.id, &observation.value, observation.timestamp)
+                    .unwrap();
+            }
+
+            db.make_index(case_sensitivity).unwrap();
+
+            assert!(db.verify_index(case_sensitivity).unwrap().is_empty());
+
+            db.db
+                .delete_cf(db.by_id_cf(), KeyCodec::encode(&1, b"bar"))
+                .unwrap();
+            db.db
+                .delete_cf(db.by_id_cf(), KeyCodec::encode(&2, b"FOO"))
+                .unwrap();
+
+            let expected = match case_sensitivity {
+                CaseSensitivity::Sensitive => vec!["FOO", "bar"],
+                CaseSensitivity::Insensitive => vec!["bar", "foo"],
+            };
+
+            assert_eq!(db.verify_index(case_sensitivity).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn make_index_incremental() {
         let dir = tempfile::tempdir().unwrap();
-        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
         for observation in observations() {
             db.put(observation.id, &observation.value, observation.timestamp)
                 .unwrap();
         }
 
-        let expected = vec![
-            ("foo".to_string(), (23, 101).into()),
-            ("bar".to_string(), (1, 1).into()),
-            ("qux".to_string(), (0, 50).into()),
-        ]
-        .into_iter()
-        .collect();
+        assert_eq!(db.index_watermark().unwrap(), None);
 
-        assert_eq!(db.get(1).unwrap(), expected);
+        db.make_index_incremental(0, CaseSensitivity::Sensitive)
+            .unwrap();
+        assert_eq!(db.index_watermark().unwrap(), Some(3));
+
+        db.put(3, "foo", 1).unwrap();
+        db.put(10, "bar", 1).unwrap();
+        db.put(10, "xyz", 1).unwrap();
+
+        let watermark = db.index_watermark().unwrap().unwrap();
+        db.make_index_incremental(watermark, CaseSensitivity::Sensitive)
+            .unwrap();
+        assert_eq!(db.index_watermark().unwrap(), Some(11));
+
+        db.make_index_incremental(11, CaseSensitivity::Sensitive)
+            .unwrap();
+        assert_eq!(db.index_watermark().unwrap(), Some(11));
+
+        let full_dir = tempfile::tempdir().unwrap();
+        let full: Hkvdb<Writeable, Set32> = Hkvdb::new(full_dir, false).unwrap();
+
+        for result in db.iter() {
+            let (id, data, value) = result.unwrap();
+            full.merge_value(id, &data, value).unwrap();
+        }
+
+        full.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(
+            db.iter_index().collect::<Result<Vec<_>, _>>().unwrap(),
+            full.iter_index().collect::<Result<Vec<_>, _>>().unwrap()
+        );
+        assert_eq!(db.search("foo").unwrap(), vec![1, 3]);
     }
 
     #[test]
-    fn timestamp_set() {
+    fn iter_and_restore_index() {
         let dir = tempfile::tempdir().unwrap();
         let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
@@ -492,19 +4646,32 @@ mod tests {
                 .unwrap();
         }
 
-        let expected = vec![
-            ("foo".to_string(), Set32::new(&[23, 101])),
-            ("bar".to_string(), Set32::new(&[1])),
-            ("qux".to_string(), Set32::new(&[0, 50])),
-        ]
-        .into_iter()
-        .collect();
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
 
-        assert_eq!(db.get(1).unwrap(), expected);
+        let entries = db.iter_index().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(
+            entries,
+            vec![
+                (b"abc".to_vec(), Set64::new(&[2])),
+                (b"bar".to_vec(), Set64::new(&[1])),
+                (b"foo".to_vec(), Set64::new(&[1, 2])),
+                (b"qux".to_vec(), Set64::new(&[1])),
+            ]
+        );
+
+        let restored_dir = tempfile::tempdir().unwrap();
+        let restored: Hkvdb<Writeable, Set32> = Hkvdb::new(restored_dir, false).unwrap();
+
+        restored.restore_index(entries).unwrap();
+
+        assert_eq!(restored.search_ci("FOO").unwrap(), vec![1, 2]);
+        assert_eq!(restored.search_ci("abc").unwrap(), vec![2]);
+        assert!(restored.search_ci("xyz").unwrap().is_empty());
     }
 
     #[test]
-    fn search() {
+    fn iter_term_ids() {
         let dir = tempfile::tempdir().unwrap();
         let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
@@ -513,16 +4680,27 @@ mod tests {
                 .unwrap();
         }
 
-        db.make_index(CaseSensitivity::Sensitive).unwrap();
+        db.make_index(CaseSensitivity::Insensitive).unwrap();
 
-        assert_eq!(db.search("foo").unwrap(), vec![1]);
+        assert_eq!(
+            db.iter_term_ids().collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![
+                ("abc".to_string(), 2),
+                ("bar".to_string(), 1),
+                ("foo".to_string(), 1),
+                ("foo".to_string(), 2),
+                ("qux".to_string(), 1),
+            ]
+        );
     }
 
     #[test]
-    fn search_ci() {
+    fn index_size_histogram() {
         let dir = tempfile::tempdir().unwrap();
         let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
 
+        assert!(db.index_size_histogram().unwrap().is_empty());
+
         for observation in observations() {
             db.put(observation.id, &observation.value, observation.timestamp)
                 .unwrap();
@@ -530,7 +4708,47 @@ mod tests {
 
         db.make_index(CaseSensitivity::Insensitive).unwrap();
 
-        assert_eq!(db.search_ci("foo").unwrap(), vec![1, 2]);
+        let expected = vec![(1, 3), (2, 1)].into_iter().collect::<BTreeMap<_, _>>();
+
+        assert_eq!(db.index_size_histogram().unwrap(), expected);
+    }
+
+    #[test]
+    fn put_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_indexed(1, "Foo", 10.into(), CaseSensitivity::Insensitive)
+            .unwrap();
+        db.put_indexed(2, "foo", 20.into(), CaseSensitivity::Insensitive)
+            .unwrap();
+
+        assert_eq!(db.get_one(1, "Foo").unwrap(), Some(10.into()));
+        assert_eq!(db.search_ci("FOO").unwrap(), vec![1, 2]);
+        assert!(db.search("Foo").unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Set32> = Hkvdb::new(dir, false).unwrap();
+
+        for observation in observations() {
+            db.put(observation.id, &observation.value, observation.timestamp)
+                .unwrap();
+        }
+
+        db.make_index(CaseSensitivity::Sensitive).unwrap();
+
+        db.put(2, "qux", 100).unwrap();
+        db.put(3, "qux", 100).unwrap();
+
+        assert_eq!(db.search("qux").unwrap(), vec![1]);
+
+        db.index_id(2, CaseSensitivity::Sensitive).unwrap();
+
+        assert_eq!(db.search("qux").unwrap(), vec![1, 2]);
+        assert_eq!(db.search("abc").unwrap(), vec![2]);
     }
 
     #[test]
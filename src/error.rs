@@ -10,10 +10,34 @@ pub enum Error {
     InvalidValue(Vec<u8>),
     #[error("Invalid UTF-8")]
     InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("Invalid UTF-8 key")]
+    InvalidUtf8Key {
+        bytes: Vec<u8>,
+        source: std::str::Utf8Error,
+    },
+    #[error("Missing column family: {0}")]
+    MissingColumnFamily(String),
+    #[error("Index is disabled")]
+    IndexDisabled,
+    #[error("Value type mismatch (expected {expected}, found {found})")]
+    ValueTypeMismatch { expected: String, found: String },
+    #[cfg(feature = "serde")]
+    #[error("JSON error")]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "async")]
+    #[error("Blocking task error")]
+    Task(#[from] tokio::task::JoinError),
 }
 
 impl Error {
     pub fn invalid_value(value: &[u8]) -> Self {
         Self::InvalidValue(value.to_vec())
     }
+
+    pub fn invalid_utf8_key(error: std::string::FromUtf8Error) -> Self {
+        Self::InvalidUtf8Key {
+            source: error.utf8_error(),
+            bytes: error.into_bytes(),
+        }
+    }
 }
@@ -0,0 +1,171 @@
+use super::{db::Hkvdb, error::Error, key::Key, table::Writeable, value::Value};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+const IMPORT_CHUNK_SIZE: usize = 10_000;
+
+/// A single row of a newline-delimited JSON dump.
+#[derive(Serialize)]
+struct Row<'a, K, V> {
+    id: &'a K,
+    key: &'a str,
+    value: &'a V,
+}
+
+#[derive(Deserialize)]
+struct OwnedRow<K, V> {
+    id: K,
+    key: String,
+    value: V,
+}
+
+impl<M, V: Value + Serialize, K: Key + Serialize> Hkvdb<M, V, K> {
+    /// Write every row in the database as a newline-delimited JSON object.
+    pub fn export_jsonl<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        for result in self.iter() {
+            let (id, key, value) = result?;
+
+            serde_json::to_writer(
+                &mut writer,
+                &Row {
+                    id: &id,
+                    key: &key,
+                    value: &value,
+                },
+            )?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<V: Value + DeserializeOwned, K: Key + DeserializeOwned> Hkvdb<Writeable, V, K> {
+    /// Read newline-delimited JSON objects (in the format written by `export_jsonl`) and merge
+    /// them into the database, returning the number of rows imported.
+    ///
+    /// If `strict` is false, malformed lines are logged and skipped instead of failing.
+    pub fn import_jsonl<R: BufRead>(&self, reader: R, strict: bool) -> Result<u64, Error> {
+        let mut count = 0;
+        let mut rows = Vec::with_capacity(IMPORT_CHUNK_SIZE);
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<OwnedRow<K, V>>(&line) {
+                Ok(row) => {
+                    rows.push((row.id, row.key, row.value));
+                    count += 1;
+                }
+                Err(error) if !strict => {
+                    log::warn!("Skipping malformed line {}: {:?}", i + 1, error);
+                }
+                Err(error) => {
+                    return Err(error.into());
+                }
+            }
+
+            if rows.len() >= IMPORT_CHUNK_SIZE {
+                self.put_batch(rows.drain(..))?;
+            }
+        }
+
+        if !rows.is_empty() {
+            self.put_batch(rows)?;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::value::Range32;
+    use super::*;
+
+    fn observations() -> Vec<(u64, &'static str, u32)> {
+        vec![
+            (1, "foo", 101),
+            (1, "bar", 1),
+            (1, "foo", 23),
+            (2, "FOO", 23),
+            (1, "qux", 50),
+            (1, "bar", 1),
+            (1, "qux", 0),
+            (2, "abc", 23),
+        ]
+    }
+
+    #[test]
+    fn export_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_batch(observations()).unwrap();
+
+        let mut output = vec![];
+        db.export_jsonl(&mut output).unwrap();
+
+        let lines = std::str::from_utf8(&output)
+            .unwrap()
+            .lines()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"id":1,"key":"bar","value":{"first":1,"last":1}}"#,
+                r#"{"id":1,"key":"foo","value":{"first":23,"last":101}}"#,
+                r#"{"id":1,"key":"qux","value":{"first":0,"last":50}}"#,
+                r#"{"id":2,"key":"FOO","value":{"first":23,"last":23}}"#,
+                r#"{"id":2,"key":"abc","value":{"first":23,"last":23}}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn import_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        db.put_batch(observations()).unwrap();
+
+        let mut output = vec![];
+        db.export_jsonl(&mut output).unwrap();
+
+        let restored_dir = tempfile::tempdir().unwrap();
+        let restored: Hkvdb<Writeable, Range32> = Hkvdb::new(restored_dir, false).unwrap();
+
+        assert_eq!(restored.import_jsonl(output.as_slice(), true).unwrap(), 5);
+        assert_eq!(
+            restored.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            db.iter().collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn import_jsonl_malformed() {
+        let input = concat!(
+            r#"{"id":1,"key":"foo","value":{"first":23,"last":101}}"#,
+            "\n",
+            "not json\n",
+            "\n",
+            r#"{"id":1,"key":"foo","value":{"first":5,"last":10}}"#,
+            "\n",
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let db: Hkvdb<Writeable, Range32> = Hkvdb::new(dir, false).unwrap();
+
+        assert!(db.import_jsonl(input.as_bytes(), true).is_err());
+        assert_eq!(db.import_jsonl(input.as_bytes(), false).unwrap(), 2);
+        assert_eq!(
+            db.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![(1, "foo".to_string(), (5, 101).into())]
+        );
+    }
+}
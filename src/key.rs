@@ -0,0 +1,117 @@
+use super::error::Error;
+use std::marker::PhantomData;
+
+/// The type of the ids that values are stored under.
+///
+/// Ids are stored as a fixed-width prefix of each key, and the byte ordering of the encoded
+/// ids should match the ordering of the ids themselves.
+pub trait Key: Sized {
+    /// The length of the encoded id in bytes.
+    const LEN: usize;
+
+    fn to_bytes(&self) -> Vec<u8>;
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+impl Key for u64 {
+    const LEN: usize = 8;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(u64::from_be_bytes(
+            bytes
+                .try_into()
+                .map_err(|_| Error::InvalidKey(bytes.to_vec()))?,
+        ))
+    }
+}
+
+/// Fixed-size byte arrays (for example UUIDs) are stored as-is.
+impl<const N: usize> Key for [u8; N] {
+    const LEN: usize = N;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        bytes
+            .try_into()
+            .map_err(|_| Error::InvalidKey(bytes.to_vec()))
+    }
+}
+
+/// The encoding of keys in the `by_id` column family.
+///
+/// Each key is the encoded id followed by the data, with no separator. Since the id has a fixed
+/// width, the data may contain any bytes (including none).
+pub struct KeyCodec<K>(PhantomData<K>);
+
+impl<K: Key> KeyCodec<K> {
+    pub fn encode(id: &K, data: &[u8]) -> Vec<u8> {
+        let mut key = Vec::with_capacity(K::LEN + data.len());
+        key.extend_from_slice(&id.to_bytes());
+        key.extend_from_slice(data);
+        key
+    }
+
+    /// The prefix shared by all keys for an id.
+    pub fn prefix(id: &K) -> Vec<u8> {
+        id.to_bytes()
+    }
+
+    /// Split a key into the encoded id and the data.
+    pub fn split(key: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+        if key.len() >= K::LEN {
+            Ok(key.split_at(K::LEN))
+        } else {
+            Err(Error::InvalidKey(key.to_vec()))
+        }
+    }
+
+    pub fn decode(key: &[u8]) -> Result<(K, &[u8]), Error> {
+        let (id_bytes, data) = Self::split(key)?;
+        let id = K::from_bytes(id_bytes).map_err(|_| Error::InvalidKey(key.to_vec()))?;
+
+        Ok((id, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_codec_round_trip() {
+        for data in [&b""[..], b"foo", &[0, 255, 0]] {
+            let key = KeyCodec::encode(&123u64, data);
+
+            assert_eq!(key.len(), 8 + data.len());
+            assert!(key.starts_with(&KeyCodec::prefix(&123u64)));
+            assert_eq!(KeyCodec::<u64>::decode(&key).unwrap(), (123, data));
+            assert_eq!(
+                KeyCodec::<u64>::split(&key).unwrap(),
+                (&123u64.to_be_bytes()[..], data)
+            );
+        }
+
+        let key = KeyCodec::encode(&[1u8, 2], b"");
+
+        assert_eq!(
+            KeyCodec::<[u8; 2]>::decode(&key).unwrap(),
+            ([1, 2], &b""[..])
+        );
+    }
+
+    #[test]
+    fn key_codec_invalid() {
+        assert!(matches!(
+            KeyCodec::<u64>::decode(&[0, 1, 2]),
+            Err(Error::InvalidKey(bytes)) if bytes == [0, 1, 2]
+        ));
+        assert!(KeyCodec::<[u8; 4]>::split(&[0, 1, 2]).is_err());
+    }
+}
@@ -1,7 +1,15 @@
+#[cfg(feature = "async")]
+pub mod async_db;
 pub mod db;
 pub mod error;
+#[cfg(feature = "serde")]
+mod jsonl;
+pub mod key;
+pub mod metrics;
+pub mod raw;
 pub mod table;
 pub mod value;
 
-pub use db::Hkvdb;
+pub use db::{Hkvdb, HkvdbBuilder};
 pub use error::Error;
+pub use raw::RawHkvdb;
@@ -0,0 +1,46 @@
+use super::error::Error;
+use std::time::{Duration, Instant};
+
+/// Receives timings for database operations.
+///
+/// All methods do nothing by default, so implementations only need to override the operations
+/// they're interested in. Batch writes are reported as a single put.
+pub trait MetricsSink: Send + Sync {
+    fn on_get(&self, _duration: Duration) {}
+    fn on_put(&self, _duration: Duration) {}
+    fn on_merge(&self, _duration: Duration) {}
+}
+
+/// Receives errors that can't be returned to the caller.
+///
+/// RocksDB doesn't allow merges to fail, so when a stored value can't be decoded during a merge,
+/// the error is logged, reported here with the name of the column family, and the last valid
+/// value is kept.
+pub trait ErrorObserver: Send + Sync {
+    fn on_merge_error(&self, column_family: &str, error: &Error);
+}
+
+pub(crate) type Callback = fn(&(dyn MetricsSink + 'static), Duration);
+
+/// Reports the time elapsed since its creation to a sink when it's dropped.
+pub(crate) struct Timer<'a> {
+    sink: &'a (dyn MetricsSink + 'static),
+    callback: Callback,
+    start: Instant,
+}
+
+impl<'a> Timer<'a> {
+    pub(crate) fn start(sink: &'a (dyn MetricsSink + 'static), callback: Callback) -> Self {
+        Self {
+            sink,
+            callback,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<'a> Drop for Timer<'a> {
+    fn drop(&mut self) {
+        (self.callback)(self.sink, self.start.elapsed());
+    }
+}
@@ -0,0 +1,194 @@
+use super::{
+    error::Error,
+    key::{Key, KeyCodec},
+    table::Table,
+};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, DB};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// A read-only view of the data in a database that doesn't depend on the value type.
+///
+/// Values are returned as the stored bytes, so this can be used by tools that need to inspect
+/// any database. Since merging requires the value type, keys with merge operands that haven't
+/// been compacted can't be read and will result in errors.
+pub struct RawHkvdb<K = u64> {
+    db: DB,
+    _key: PhantomData<K>,
+}
+
+impl<K: Key> RawHkvdb<K> {
+    /// Open the data in an existing database as read-only.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let db = DB::open_cf_descriptors_read_only(
+            &Options::default(),
+            path,
+            vec![ColumnFamilyDescriptor::new("by_id", Options::default())],
+            false,
+        )?;
+
+        Ok(Self {
+            db,
+            _key: PhantomData,
+        })
+    }
+
+    /// Read the stored bytes for all keys for an id.
+    pub fn get_raw_bytes(&self, id: K) -> Result<HashMap<Vec<u8>, Vec<u8>>, Error> {
+        read_raw_bytes(&self.db, self.by_id_cf(), &id)
+    }
+
+    /// Iterate over all ids, keys, and stored value bytes.
+    pub fn iter_raw_bytes(
+        &self,
+    ) -> impl Iterator<Item = Result<(K, Vec<u8>, Vec<u8>), Error>> + '_ {
+        self.db
+            .iterator_cf(self.by_id_cf(), IteratorMode::Start)
+            .map(|result| {
+                let (key, value_bytes) = result?;
+                let (id, data) = KeyCodec::decode(&key)?;
+
+                Ok((id, data.to_vec(), value_bytes.into_vec()))
+            })
+    }
+
+    /// The smallest and largest ids in the database, or `None` if it's empty.
+    pub fn id_bounds(&self) -> Result<Option<(K, K)>, Error> {
+        read_id_bounds(&self.db, self.by_id_cf())
+    }
+
+    fn by_id_cf(&self) -> &ColumnFamily {
+        self.db.cf_handle("by_id").unwrap()
+    }
+}
+
+impl<K: Key> Table for RawHkvdb<K> {
+    type Counts = (u64, u64);
+
+    fn underlying(&self) -> &DB {
+        &self.db
+    }
+
+    fn get_counts(&self) -> Result<Self::Counts, Error> {
+        count_ids_and_values::<K>(&self.db, self.by_id_cf())
+    }
+}
+
+pub(crate) fn read_raw_bytes<K: Key>(
+    db: &DB,
+    by_id_cf: &ColumnFamily,
+    id: &K,
+) -> Result<HashMap<Vec<u8>, Vec<u8>>, Error> {
+    let prefix = KeyCodec::prefix(id);
+    let mut results = HashMap::new();
+    let iter = db.prefix_iterator_cf(by_id_cf, &prefix);
+
+    for result in iter {
+        let (key, value_bytes) = result?;
+
+        let (id_bytes, data) = KeyCodec::<K>::split(&key)?;
+
+        if id_bytes == prefix {
+            results.insert(data.to_vec(), value_bytes.to_vec());
+        } else {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+pub(crate) fn read_id_bounds<K: Key>(
+    db: &DB,
+    by_id_cf: &ColumnFamily,
+) -> Result<Option<(K, K)>, Error> {
+    let first = db.iterator_cf(by_id_cf, IteratorMode::Start).next();
+    let last = db.iterator_cf(by_id_cf, IteratorMode::End).next();
+
+    match (first, last) {
+        (Some(first), Some(last)) => {
+            let (first, _) = first?;
+            let (last, _) = last?;
+
+            let (first, _) = KeyCodec::decode(&first)?;
+            let (last, _) = KeyCodec::decode(&last)?;
+
+            Ok(Some((first, last)))
+        }
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn count_ids_and_values<K: Key>(
+    db: &DB,
+    by_id_cf: &ColumnFamily,
+) -> Result<(u64, u64), Error> {
+    let mut id_count = 0;
+    let mut value_count = 0;
+    let mut last_id: Option<Vec<u8>> = None;
+
+    let iter = db.iterator_cf(by_id_cf, IteratorMode::Start);
+
+    // Keys are sorted, so all keys for an id are adjacent.
+    for result in iter {
+        let (key, _) = result?;
+        let (id, _) = KeyCodec::<K>::split(&key)?;
+
+        if last_id.as_deref() != Some(id) {
+            id_count += 1;
+            last_id = Some(id.to_vec());
+        }
+
+        value_count += 1;
+    }
+
+    Ok((id_count, value_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{table::Writeable, value::Range32, Hkvdb};
+
+    #[test]
+    fn read_hkvdb() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let db: Hkvdb<Writeable, Range32> = Hkvdb::new(&dir, false).unwrap();
+
+            db.put(1, "foo", 101).unwrap();
+            db.put(1, "foo", 23).unwrap();
+            db.put(1, "bar", 1).unwrap();
+            db.put(2, "FOO", 23).unwrap();
+
+            // Merge operands can't be read without the value type.
+            db.raw_db()
+                .compact_range_cf::<&[u8], &[u8]>(db.by_id_cf_handle().unwrap(), None, None);
+        }
+
+        let db: RawHkvdb = RawHkvdb::open(&dir).unwrap();
+
+        let expected = HashMap::from([
+            (b"bar".to_vec(), Vec::from(Range32::new(1, 1))),
+            (b"foo".to_vec(), Vec::from(Range32::new(23, 101))),
+        ]);
+
+        assert_eq!(db.get_raw_bytes(1).unwrap(), expected);
+        assert!(db.get_raw_bytes(3).unwrap().is_empty());
+        assert_eq!(db.id_bounds().unwrap(), Some((1, 2)));
+        assert_eq!(db.get_counts().unwrap(), (2, 3));
+        assert_eq!(
+            db.iter_raw_bytes()
+                .map(|result| result.map(|(id, data, _)| (id, data)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![
+                (1, b"bar".to_vec()),
+                (1, b"foo".to_vec()),
+                (2, b"FOO".to_vec())
+            ]
+        );
+    }
+}
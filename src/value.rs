@@ -1,27 +1,85 @@
 use super::error::Error;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::ops::Add;
 
 type MaybeBytes = Option<Vec<u8>>;
 
 /// A convenience trait that bundles up the operations needed for values.
-pub trait Value: Add<Output = Self> + Into<Vec<u8>> + Sized {
-    /// This is a hack because I couldn't figure out how to just use `TryFrom` directly.
-    fn prepare(bytes: &[u8]) -> Result<Self, Error>;
+pub trait Value:
+    Add<Output = Self> + Into<Vec<u8>> + for<'a> TryFrom<&'a [u8], Error = Error> + Sized
+{
+    /// A stable name for the value type, which is the basis of the tag recorded when a database
+    /// is created and checked when it's reopened, since merging with a different value type
+    /// would corrupt data.
+    ///
+    /// This should be unique among value types and should never change once databases exist.
+    const TYPE_TAG: &'static str;
+
+    /// The tag that's recorded for the value type, which also includes any parameters that
+    /// change how values are merged.
+    ///
+    /// By default this is just [`Value::TYPE_TAG`].
+    fn type_tag() -> Cow<'static, str> {
+        Cow::Borrowed(Self::TYPE_TAG)
+    }
+
+    /// Whether merging this value would have no effect, in which case writes can be skipped.
+    fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The expected length of the serialized value in bytes, or zero if it's not known.
+    fn byte_len_hint(&self) -> usize {
+        0
+    }
+
+    /// Whether the value only contains observations from before the cutoff.
+    ///
+    /// This is used to remove old values during compaction when an expiry cutoff is configured.
+    /// Values that don't track timestamps never expire.
+    fn is_expired(&self, _cutoff: u32) -> bool {
+        false
+    }
+
+    /// Remove the observations from before the cutoff, for value types that store them
+    /// individually.
+    ///
+    /// This is applied to merged values when an expiry cutoff is configured. By default values
+    /// are kept whole.
+    fn expire(self, _cutoff: u32) -> Self {
+        self
+    }
+
+    /// The most recent observation timestamp in the value, if it tracks timestamps.
+    ///
+    /// This is used to record when index terms were last seen.
+    fn last_timestamp(&self) -> Option<u32> {
+        None
+    }
+
+    /// Check whether the bytes are a valid serialized value.
+    ///
+    /// By default this deserializes the value and discards it, but types can override it with a
+    /// cheaper check that doesn't allocate.
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        Self::try_from(bytes).map(|_| ())
+    }
 
     fn merge<'a, I: Iterator<Item = &'a [u8]>>(
         existing: Option<&[u8]>,
         new_values: I,
     ) -> Result<MaybeBytes, (Error, MaybeBytes)> {
-        let mut aggregated = match existing.map_or(Ok(None), |value| Self::prepare(value).map(Some))
-        {
-            Ok(value) => value,
-            Err(error) => {
-                return Err((error, new_values.last().map(|last| last.to_vec())));
-            }
-        };
+        let mut aggregated =
+            match existing.map_or(Ok(None), |value| Self::try_from(value).map(Some)) {
+                Ok(value) => value,
+                Err(error) => {
+                    return Err((error, new_values.last().map(|last| last.to_vec())));
+                }
+            };
 
         for bytes in new_values {
-            let prepared = match Self::prepare(bytes) {
+            let prepared = match Self::try_from(bytes) {
                 Ok(value) => value,
                 Err(error) => {
                     return Err((error, aggregated.map(Self::into)));
@@ -38,10 +96,38 @@ pub trait Value: Add<Output = Self> + Into<Vec<u8>> + Sized {
     }
 }
 
+/// A value that aggregates observation timestamps.
+pub trait Timestamps: Value + From<u32> {}
+
+/// The unit of timestamps, for converting them to dates.
+#[cfg(feature = "chrono")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeUnit {
+    Seconds,
+    Millis,
+}
+
+#[cfg(feature = "chrono")]
+impl TimeUnit {
+    fn to_datetime(self, timestamp: u32) -> chrono::DateTime<chrono::Utc> {
+        use chrono::TimeZone;
+
+        let timestamp = i64::from(timestamp);
+        let result = match self {
+            Self::Seconds => chrono::Utc.timestamp_opt(timestamp, 0),
+            Self::Millis => chrono::Utc.timestamp_millis_opt(timestamp),
+        };
+
+        // Every `u32` timestamp is in the supported range for either unit.
+        result.unwrap()
+    }
+}
+
 /// Represents a time range.
 ///
 /// The values will generally be epoch seconds, but this isn't necessary.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Range32 {
     first: u32,
     last: u32,
@@ -56,6 +142,16 @@ impl Range32 {
         Self::new(value, value)
     }
 
+    /// The smallest range containing all of the timestamps, or `None` if there are none.
+    pub fn from_timestamps<I: IntoIterator<Item = u32>>(timestamps: I) -> Option<Self> {
+        let mut timestamps = timestamps.into_iter();
+        let first = timestamps.next()?;
+
+        Some(timestamps.fold(Self::singleton(first), |range, timestamp| {
+            Self::new(range.first.min(timestamp), range.last.max(timestamp))
+        }))
+    }
+
     pub fn first(&self) -> u32 {
         self.first
     }
@@ -63,6 +159,37 @@ impl Range32 {
     pub fn last(&self) -> u32 {
         self.last
     }
+
+    /// The distance between the first and last timestamps (zero if the range is inverted).
+    pub fn span(&self) -> u32 {
+        self.last.saturating_sub(self.first)
+    }
+
+    /// The timestamp halfway between the first and last (rounded down).
+    pub fn midpoint(&self) -> u32 {
+        self.first + self.span() / 2
+    }
+
+    /// The length of the serialized value in bytes.
+    pub fn serialized_len(&self) -> usize {
+        8
+    }
+
+    /// Whether the bytes have a valid length for a serialized `Range32`.
+    pub fn is_valid_len(bytes: &[u8]) -> bool {
+        bytes.len() == 8
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Range32 {
+    /// The first and last timestamps as dates.
+    pub fn to_datetime_range(
+        &self,
+        unit: TimeUnit,
+    ) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
+        (unit.to_datetime(self.first), unit.to_datetime(self.last))
+    }
 }
 
 impl From<(u32, u32)> for Range32 {
@@ -98,7 +225,7 @@ impl TryFrom<&[u8]> for Range32 {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        if bytes.len() == 8 {
+        if Self::is_valid_len(bytes) {
             let first = u32::from_be_bytes(
                 bytes[0..4]
                     .try_into()
@@ -118,11 +245,266 @@ impl TryFrom<&[u8]> for Range32 {
 }
 
 impl Value for Range32 {
-    fn prepare(bytes: &[u8]) -> Result<Self, Error> {
-        Self::try_from(bytes)
+    const TYPE_TAG: &'static str = "Range32";
+
+    fn byte_len_hint(&self) -> usize {
+        self.serialized_len()
+    }
+
+    fn last_timestamp(&self) -> Option<u32> {
+        Some(self.last)
+    }
+
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        if Self::is_valid_len(bytes) {
+            Ok(())
+        } else {
+            Err(Error::invalid_value(bytes))
+        }
+    }
+
+    fn is_expired(&self, cutoff: u32) -> bool {
+        self.last < cutoff
+    }
+}
+
+impl Timestamps for Range32 {}
+
+/// Represents a time range along with the number of observations in it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct RangeCount {
+    first: u32,
+    last: u32,
+    count: u64,
+}
+
+impl RangeCount {
+    pub fn new(first: u32, last: u32, count: u64) -> Self {
+        Self { first, last, count }
+    }
+
+    pub fn first(&self) -> u32 {
+        self.first
+    }
+
+    pub fn last(&self) -> u32 {
+        self.last
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// A timestamp and the number of times it was observed.
+impl From<(u32, u64)> for RangeCount {
+    fn from(input: (u32, u64)) -> Self {
+        Self::new(input.0, input.0, input.1)
+    }
+}
+
+impl From<u32> for RangeCount {
+    fn from(input: u32) -> Self {
+        Self::new(input, input, 1)
+    }
+}
+
+impl Add for RangeCount {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Self::new(
+            self.first.min(other.first),
+            self.last.max(other.last),
+            self.count.saturating_add(other.count),
+        )
+    }
+}
+
+impl From<RangeCount> for Vec<u8> {
+    fn from(input: RangeCount) -> Self {
+        let mut result = Vec::with_capacity(16);
+        result.extend_from_slice(&input.first.to_be_bytes());
+        result.extend_from_slice(&input.last.to_be_bytes());
+        result.extend_from_slice(&input.count.to_be_bytes());
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for RangeCount {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() == 16 {
+            let first = u32::from_be_bytes(
+                bytes[0..4]
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(bytes))?,
+            );
+            let last = u32::from_be_bytes(
+                bytes[4..8]
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(bytes))?,
+            );
+            let count = u64::from_be_bytes(
+                bytes[8..16]
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(bytes))?,
+            );
+
+            Ok(Self { first, last, count })
+        } else {
+            Err(Error::invalid_value(bytes))
+        }
+    }
+}
+
+impl Value for RangeCount {
+    const TYPE_TAG: &'static str = "RangeCount";
+
+    fn byte_len_hint(&self) -> usize {
+        16
+    }
+
+    fn last_timestamp(&self) -> Option<u32> {
+        Some(self.last)
+    }
+}
+
+impl Timestamps for RangeCount {}
+
+/// Represents a set of time ranges separated by gaps.
+///
+/// Ranges are merged when the second starts no more than `GAP` after the first ends, so
+/// observations that are close together are combined into a single window of activity.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ranges32<const GAP: u32> {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl<const GAP: u32> Ranges32<GAP> {
+    pub fn new(ranges: &[(u32, u32)]) -> Self {
+        let mut ranges = ranges
+            .iter()
+            .map(|(first, last)| (*first.min(last), *first.max(last)))
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+
+        Self {
+            ranges: Self::coalesce(ranges),
+        }
+    }
+
+    pub fn singleton(value: u32) -> Self {
+        Self {
+            ranges: vec![(value, value)],
+        }
+    }
+
+    pub fn ranges(&self) -> &[(u32, u32)] {
+        &self.ranges
+    }
+
+    /// Merge close ranges in a list that's sorted by start.
+    fn coalesce(sorted: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+        let mut result: Vec<(u32, u32)> = Vec::with_capacity(sorted.len());
+
+        for (first, last) in sorted {
+            match result.last_mut() {
+                Some(current) if first <= current.1.saturating_add(GAP) => {
+                    current.1 = current.1.max(last);
+                }
+                _ => result.push((first, last)),
+            }
+        }
+
+        result
+    }
+}
+
+impl<const GAP: u32> From<u32> for Ranges32<GAP> {
+    fn from(input: u32) -> Self {
+        Self::singleton(input)
+    }
+}
+
+impl<const GAP: u32> Add for Ranges32<GAP> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        let mut ranges = self.ranges;
+        ranges.extend(other.ranges);
+        ranges.sort_unstable();
+
+        Self {
+            ranges: Self::coalesce(ranges),
+        }
+    }
+}
+
+impl<const GAP: u32> From<Ranges32<GAP>> for Vec<u8> {
+    fn from(input: Ranges32<GAP>) -> Self {
+        let mut result = Vec::with_capacity(8 * input.ranges.len());
+        for (first, last) in input.ranges {
+            result.extend_from_slice(&first.to_be_bytes());
+            result.extend_from_slice(&last.to_be_bytes());
+        }
+        result
+    }
+}
+
+impl<const GAP: u32> TryFrom<&[u8]> for Ranges32<GAP> {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let chunks = bytes.chunks_exact(8);
+
+        if !chunks.remainder().is_empty() {
+            return Err(Error::invalid_value(bytes));
+        }
+
+        let mut ranges: Vec<(u32, u32)> = Vec::with_capacity(chunks.len());
+
+        for chunk in chunks {
+            let range = Range32::try_from(chunk).map_err(|_| Error::invalid_value(bytes))?;
+
+            // Ranges must be well-formed, sorted, and separated.
+            if range.first > range.last
+                || matches!(ranges.last(), Some((_, last)) if range.first <= *last)
+            {
+                return Err(Error::invalid_value(bytes));
+            }
+
+            ranges.push((range.first, range.last));
+        }
+
+        Ok(Self { ranges })
+    }
+}
+
+impl<const GAP: u32> Value for Ranges32<GAP> {
+    const TYPE_TAG: &'static str = "Ranges32";
+
+    fn type_tag() -> Cow<'static, str> {
+        Cow::Owned(format!("{}/{}", Self::TYPE_TAG, GAP))
+    }
+
+    fn byte_len_hint(&self) -> usize {
+        8 * self.ranges.len()
+    }
+
+    fn last_timestamp(&self) -> Option<u32> {
+        self.ranges.iter().map(|(_, last)| *last).max()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
     }
 }
 
+impl<const GAP: u32> Timestamps for Ranges32<GAP> {}
+
 /// Represents a set of time observations as a sorted, deduplicated sequence.
 ///
 /// The values will generally be epoch seconds, but this isn't necessary.
@@ -150,6 +532,30 @@ impl Set32 {
     pub fn into_inner(self) -> Vec<u32> {
         self.values
     }
+
+    /// The length of the serialized value in bytes.
+    pub fn serialized_len(&self) -> usize {
+        4 * self.values.len()
+    }
+
+    /// Whether the bytes have a valid length for a serialized `Set32`.
+    pub fn is_valid_len(bytes: &[u8]) -> bool {
+        bytes.chunks_exact(4).remainder().is_empty()
+    }
+
+    /// The values that are in both sets.
+    pub fn intersect(&self, other: &Set32) -> Set32 {
+        Self {
+            values: intersect_sorted(&self.values, &other.values),
+        }
+    }
+
+    /// The values that are in this set but not the other.
+    pub fn difference(&self, other: &Set32) -> Set32 {
+        Self {
+            values: difference_sorted(&self.values, &other.values),
+        }
+    }
 }
 
 impl From<&[u32]> for Set32 {
@@ -164,6 +570,24 @@ impl From<u32> for Set32 {
     }
 }
 
+impl IntoIterator for Set32 {
+    type Item = u32;
+    type IntoIter = std::vec::IntoIter<u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Set32 {
+    type Item = &'a u32;
+    type IntoIter = std::slice::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
 impl Add for Set32 {
     type Output = Self;
 
@@ -191,7 +615,7 @@ impl TryFrom<&[u8]> for Set32 {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        if bytes.len() % 4 == 0 {
+        if Self::is_valid_len(bytes) {
             let len = bytes.len() / 4;
             let mut result = Vec::with_capacity(len);
 
@@ -213,10 +637,56 @@ impl TryFrom<&[u8]> for Set32 {
 }
 
 impl Value for Set32 {
-    fn prepare(bytes: &[u8]) -> Result<Self, Error> {
-        Self::try_from(bytes)
+    const TYPE_TAG: &'static str = "Set32";
+
+    fn byte_len_hint(&self) -> usize {
+        self.serialized_len()
+    }
+
+    fn last_timestamp(&self) -> Option<u32> {
+        self.values.last().copied()
+    }
+
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        if Self::is_valid_len(bytes) {
+            Ok(())
+        } else {
+            Err(Error::invalid_value(bytes))
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn is_expired(&self, cutoff: u32) -> bool {
+        matches!(self.values.last(), Some(last) if *last < cutoff)
+    }
+
+    fn expire(mut self, cutoff: u32) -> Self {
+        self.values.retain(|value| *value >= cutoff);
+        self
+    }
+}
+
+/// Serialized as an array of the values.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Set32 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.values)
+    }
+}
+
+/// Deserialized from an array of values, which don't need to be sorted or unique.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Set32 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u32>::deserialize(deserializer).map(|values| Self::new(&values))
     }
 }
+
+impl Timestamps for Set32 {}
+
 /// Represents a set of unsigned integers.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Set64 {
@@ -235,6 +705,16 @@ impl Set64 {
         Self::new(&[value])
     }
 
+    /// Create a set from values that are already sorted and deduplicated.
+    pub(crate) fn from_sorted_unchecked(values: Vec<u64>) -> Self {
+        debug_assert!(
+            values.windows(2).all(|pair| pair[0] < pair[1]),
+            "Set64 values must be sorted and unique"
+        );
+
+        Self { values }
+    }
+
     pub fn values(&self) -> &[u64] {
         &self.values
     }
@@ -242,6 +722,26 @@ impl Set64 {
     pub fn into_inner(self) -> Vec<u64> {
         self.values
     }
+
+    /// The length of the serialized value in bytes.
+    pub fn serialized_len(&self) -> usize {
+        8 * self.values.len()
+    }
+
+    /// Whether the bytes have a valid length for a serialized `Set64`.
+    pub fn is_valid_len(bytes: &[u8]) -> bool {
+        bytes.chunks_exact(8).remainder().is_empty()
+    }
+
+    /// The values that are in both sets.
+    pub fn intersect(&self, other: &Set64) -> Set64 {
+        Self::from_sorted_unchecked(intersect_sorted(&self.values, &other.values))
+    }
+
+    /// The values that are in this set but not the other.
+    pub fn difference(&self, other: &Set64) -> Set64 {
+        Self::from_sorted_unchecked(difference_sorted(&self.values, &other.values))
+    }
 }
 
 impl From<&[u64]> for Set64 {
@@ -256,6 +756,24 @@ impl From<u64> for Set64 {
     }
 }
 
+impl IntoIterator for Set64 {
+    type Item = u64;
+    type IntoIter = std::vec::IntoIter<u64>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Set64 {
+    type Item = &'a u64;
+    type IntoIter = std::slice::Iter<'a, u64>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
 impl Add for Set64 {
     type Output = Self;
 
@@ -283,7 +801,7 @@ impl TryFrom<&[u8]> for Set64 {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        if bytes.len() % 8 == 0 {
+        if Self::is_valid_len(bytes) {
             let len = bytes.len() / 8;
             let mut result = Vec::with_capacity(len);
 
@@ -297,7 +815,8 @@ impl TryFrom<&[u8]> for Set64 {
                 result.push(value);
             }
 
-            Ok(Self { values: result })
+            // Stored sets are always written in sorted order.
+            Ok(Self::from_sorted_unchecked(result))
         } else {
             Err(Error::invalid_value(bytes))
         }
@@ -305,7 +824,1536 @@ impl TryFrom<&[u8]> for Set64 {
 }
 
 impl Value for Set64 {
-    fn prepare(bytes: &[u8]) -> Result<Self, Error> {
-        Self::try_from(bytes)
+    const TYPE_TAG: &'static str = "Set64";
+
+    fn byte_len_hint(&self) -> usize {
+        self.serialized_len()
+    }
+
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        // Big-endian encoding preserves order, so the values can be compared as bytes.
+        let mut chunks = bytes.chunks_exact(8);
+        let is_sorted = chunks
+            .clone()
+            .zip(chunks.by_ref().skip(1))
+            .all(|(previous, next)| previous < next);
+
+        if is_sorted && chunks.remainder().is_empty() {
+            Ok(())
+        } else {
+            Err(Error::invalid_value(bytes))
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Serialized as an array of the values.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Set64 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.values)
+    }
+}
+
+/// Deserialized from an array of values, which don't need to be sorted or unique.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Set64 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u64>::deserialize(deserializer).map(|values| Self::new(&values))
+    }
+}
+
+/// Intersect two sorted, deduplicated slices in linear time.
+fn intersect_sorted<T: Copy + Ord>(left: &[T], right: &[T]) -> Vec<T> {
+    let mut result = Vec::with_capacity(left.len().min(right.len()));
+    let mut i = 0;
+    let mut j = 0;
+
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// Subtract one sorted, deduplicated slice from another in linear time.
+fn difference_sorted<T: Copy + Ord>(left: &[T], right: &[T]) -> Vec<T> {
+    let mut result = Vec::with_capacity(left.len());
+    let mut j = 0;
+
+    for value in left {
+        while j < right.len() && right[j] < *value {
+            j += 1;
+        }
+
+        if j >= right.len() || right[j] != *value {
+            result.push(*value);
+        }
+    }
+
+    result
+}
+
+/// Represents changes to a set of unsigned integers, supporting both additions and removals.
+///
+/// Each value is a set of additions and a disjoint set of removals, and adding two values applies
+/// the changes in the second after those in the first. This means that ids can be removed with
+/// a merge, without reading the current value. Since operands may be combined before they're
+/// applied to the stored value, removals are kept as tombstones in the stored value.
+///
+/// Each element is serialized as a tag byte (`0` for an addition and `1` for a removal) followed
+/// by the big-endian value.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DiffSet64 {
+    added: Vec<u64>,
+    removed: Vec<u64>,
+}
+
+impl DiffSet64 {
+    const ADDED_TAG: u8 = 0;
+    const REMOVED_TAG: u8 = 1;
+
+    pub fn addition(value: u64) -> Self {
+        Self {
+            added: vec![value],
+            removed: vec![],
+        }
+    }
+
+    pub fn removal(value: u64) -> Self {
+        Self {
+            added: vec![],
+            removed: vec![value],
+        }
+    }
+
+    /// The values in the set after all changes have been applied.
+    pub fn values(&self) -> &[u64] {
+        &self.added
+    }
+
+    /// The values that have been removed and not added again.
+    pub fn removed(&self) -> &[u64] {
+        &self.removed
+    }
+}
+
+impl From<u64> for DiffSet64 {
+    fn from(input: u64) -> Self {
+        Self::addition(input)
+    }
+}
+
+impl Add for DiffSet64 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        let mut added = difference_sorted(&self.added, &other.removed);
+        added.extend(other.added.iter().copied());
+        added.sort_unstable();
+        added.dedup();
+
+        let mut removed = difference_sorted(&self.removed, &other.added);
+        removed.extend(other.removed);
+        removed.sort_unstable();
+        removed.dedup();
+
+        Self { added, removed }
+    }
+}
+
+impl From<DiffSet64> for Vec<u8> {
+    fn from(input: DiffSet64) -> Self {
+        let mut result = Vec::with_capacity(9 * (input.added.len() + input.removed.len()));
+
+        for value in input.added {
+            result.push(DiffSet64::ADDED_TAG);
+            result.extend_from_slice(&value.to_be_bytes());
+        }
+
+        for value in input.removed {
+            result.push(DiffSet64::REMOVED_TAG);
+            result.extend_from_slice(&value.to_be_bytes());
+        }
+
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for DiffSet64 {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let chunks = bytes.chunks_exact(9);
+
+        if !chunks.remainder().is_empty() {
+            return Err(Error::invalid_value(bytes));
+        }
+
+        // Elements are applied in order, so a later change to a value overrides an earlier one.
+        let mut changes = BTreeMap::new();
+
+        for chunk in chunks {
+            let value = u64::from_be_bytes(
+                chunk[1..9]
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(bytes))?,
+            );
+
+            match chunk[0] {
+                Self::ADDED_TAG => changes.insert(value, true),
+                Self::REMOVED_TAG => changes.insert(value, false),
+                _ => return Err(Error::invalid_value(bytes)),
+            };
+        }
+
+        let mut result = Self::default();
+
+        for (value, is_added) in changes {
+            if is_added {
+                result.added.push(value);
+            } else {
+                result.removed.push(value);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl Value for DiffSet64 {
+    const TYPE_TAG: &'static str = "DiffSet64";
+
+    fn byte_len_hint(&self) -> usize {
+        9 * (self.added.len() + self.removed.len())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Represents a set of unsigned integers with a compact serialization.
+///
+/// The sorted values are stored as a varint-encoded first value followed by varint-encoded
+/// deltas, which is much smaller than `Set64` when the values are clustered.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompactSet64 {
+    values: Vec<u64>,
+}
+
+impl CompactSet64 {
+    pub fn new(values: &[u64]) -> Self {
+        let mut values = values.to_vec();
+        values.sort_unstable();
+        values.dedup();
+        Self { values }
+    }
+
+    pub fn singleton(value: u64) -> Self {
+        Self::new(&[value])
+    }
+
+    pub fn values(&self) -> &[u64] {
+        &self.values
+    }
+
+    pub fn into_inner(self) -> Vec<u64> {
+        self.values
+    }
+}
+
+impl From<&[u64]> for CompactSet64 {
+    fn from(input: &[u64]) -> Self {
+        Self::new(input)
+    }
+}
+
+impl From<u64> for CompactSet64 {
+    fn from(input: u64) -> Self {
+        Self::singleton(input)
+    }
+}
+
+impl Add for CompactSet64 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        let mut values = Vec::with_capacity(self.values.len() + other.values.len());
+        values.extend(self.values);
+        values.extend(other.values);
+        values.sort_unstable();
+        values.dedup();
+        Self { values }
+    }
+}
+
+impl From<CompactSet64> for Vec<u8> {
+    fn from(input: CompactSet64) -> Self {
+        let mut result = Vec::with_capacity(input.values.len());
+        let mut previous = 0;
+        for value in input.values {
+            write_varint(value - previous, &mut result);
+            previous = value;
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for CompactSet64 {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut result: Vec<u64> = Vec::new();
+        let mut remaining = bytes;
+
+        while !remaining.is_empty() {
+            let (delta, rest) =
+                read_varint(remaining).ok_or_else(|| Error::invalid_value(bytes))?;
+
+            let value = match result.last() {
+                // Deltas after the first value must be positive, since the values are unique.
+                Some(previous) if delta > 0 => previous
+                    .checked_add(delta)
+                    .ok_or_else(|| Error::invalid_value(bytes))?,
+                Some(_) => return Err(Error::invalid_value(bytes)),
+                None => delta,
+            };
+
+            result.push(value);
+            remaining = rest;
+        }
+
+        Ok(Self { values: result })
+    }
+}
+
+impl Value for CompactSet64 {
+    const TYPE_TAG: &'static str = "CompactSet64";
+
+    fn byte_len_hint(&self) -> usize {
+        let mut previous = 0;
+
+        self.values
+            .iter()
+            .map(|value| {
+                let len = varint_len(value - previous);
+                previous = *value;
+                len
+            })
+            .sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The length of an unsigned LEB128 varint.
+fn varint_len(value: u64) -> usize {
+    let bits = (64 - value.leading_zeros()).max(1) as usize;
+    bits.div_ceil(7)
+}
+
+/// Write an unsigned LEB128 varint.
+fn write_varint(mut value: u64, output: &mut Vec<u8>) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+/// Read an unsigned LEB128 varint, returning the value and the remaining bytes.
+fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value: u64 = 0;
+
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        let bits = u64::from(byte & 0x7f);
+
+        // The tenth byte can only contribute the highest bit.
+        if i == 9 && bits > 1 {
+            return None;
+        }
+
+        value |= bits << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+
+    None
+}
+
+/// Represents counts of observations grouped into buckets.
+///
+/// The buckets will generally be derived from timestamps (e.g. days since the epoch), but this
+/// isn't necessary. Counts saturate at `u32::MAX`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Histogram {
+    buckets: BTreeMap<u32, u32>,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn singleton(bucket: u32) -> Self {
+        Self::from((bucket, 1))
+    }
+
+    pub fn count(&self, bucket: u32) -> u32 {
+        self.buckets.get(&bucket).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.buckets.values().map(|count| u64::from(*count)).sum()
+    }
+
+    pub fn buckets(&self) -> &BTreeMap<u32, u32> {
+        &self.buckets
+    }
+
+    pub fn into_inner(self) -> BTreeMap<u32, u32> {
+        self.buckets
+    }
+}
+
+impl From<u32> for Histogram {
+    fn from(input: u32) -> Self {
+        Self::singleton(input)
+    }
+}
+
+impl From<(u32, u32)> for Histogram {
+    fn from(input: (u32, u32)) -> Self {
+        let mut buckets = BTreeMap::new();
+        buckets.insert(input.0, input.1);
+        Self { buckets }
+    }
+}
+
+impl Add for Histogram {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        let mut buckets = self.buckets;
+
+        for (bucket, count) in other.buckets {
+            let current = buckets.entry(bucket).or_insert(0);
+            *current = current.saturating_add(count);
+        }
+
+        Self { buckets }
+    }
+}
+
+impl From<Histogram> for Vec<u8> {
+    fn from(input: Histogram) -> Self {
+        let mut result = Vec::with_capacity(8 * input.buckets.len());
+        for (bucket, count) in input.buckets {
+            result.extend_from_slice(&bucket.to_be_bytes());
+            result.extend_from_slice(&count.to_be_bytes());
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Histogram {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let chunks = bytes.chunks_exact(8);
+
+        if !chunks.remainder().is_empty() {
+            return Err(Error::invalid_value(bytes));
+        }
+
+        let mut buckets = BTreeMap::new();
+        let mut last_bucket = None;
+
+        for chunk in chunks {
+            let bucket = u32::from_be_bytes(
+                chunk[0..4]
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(bytes))?,
+            );
+            let count = u32::from_be_bytes(
+                chunk[4..8]
+                    .try_into()
+                    .map_err(|_| Error::invalid_value(bytes))?,
+            );
+
+            // Buckets are serialized in strictly increasing order.
+            if matches!(last_bucket, Some(last_bucket) if bucket <= last_bucket) {
+                return Err(Error::invalid_value(bytes));
+            }
+
+            buckets.insert(bucket, count);
+            last_bucket = Some(bucket);
+        }
+
+        Ok(Self { buckets })
+    }
+}
+
+impl Value for Histogram {
+    const TYPE_TAG: &'static str = "Histogram";
+
+    fn byte_len_hint(&self) -> usize {
+        8 * self.buckets.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+/// Represents a sum of unsigned integers.
+///
+/// Inputs will generally be 32-bit values, which are accumulated into a 64-bit total that
+/// can't realistically overflow (the sum saturates at `u64::MAX`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sum64(u64);
+
+impl Sum64 {
+    pub fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u32> for Sum64 {
+    fn from(input: u32) -> Self {
+        Self(input.into())
+    }
+}
+
+impl From<u64> for Sum64 {
+    fn from(input: u64) -> Self {
+        Self(input)
+    }
+}
+
+impl Add for Sum64 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Self(self.0.saturating_add(other.0))
+    }
+}
+
+impl From<Sum64> for Vec<u8> {
+    fn from(input: Sum64) -> Self {
+        input.0.to_be_bytes().to_vec()
+    }
+}
+
+impl TryFrom<&[u8]> for Sum64 {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(u64::from_be_bytes(
+            bytes.try_into().map_err(|_| Error::invalid_value(bytes))?,
+        )))
+    }
+}
+
+impl Value for Sum64 {
+    const TYPE_TAG: &'static str = "Sum64";
+
+    fn byte_len_hint(&self) -> usize {
+        8
+    }
+}
+
+/// Represents a sum of unsigned integers that saturates at `u32::MAX`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SaturatingSum32(u32);
+
+impl SaturatingSum32 {
+    pub fn new(value: u32) -> Self {
+        Self(value)
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for SaturatingSum32 {
+    fn from(input: u32) -> Self {
+        Self(input)
+    }
+}
+
+impl Add for SaturatingSum32 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Self(self.0.saturating_add(other.0))
+    }
+}
+
+impl From<SaturatingSum32> for Vec<u8> {
+    fn from(input: SaturatingSum32) -> Self {
+        input.0.to_be_bytes().to_vec()
+    }
+}
+
+impl TryFrom<&[u8]> for SaturatingSum32 {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(u32::from_be_bytes(
+            bytes.try_into().map_err(|_| Error::invalid_value(bytes))?,
+        )))
+    }
+}
+
+impl Value for SaturatingSum32 {
+    const TYPE_TAG: &'static str = "SaturatingSum32";
+
+    fn byte_len_hint(&self) -> usize {
+        4
+    }
+}
+
+/// Represents a score that decays exponentially over time, with the given half-life.
+///
+/// Scores are combined by decaying the older score to the more recent timestamp and adding them,
+/// so each observation (with a score of one) contributes less the older it is. The half-life
+/// uses the same units as the timestamps, and a half-life of zero is a compile-time error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecayScore<const HALF_LIFE: u32> {
+    score: f32,
+    last_ts: u32,
+}
+
+impl<const HALF_LIFE: u32> DecayScore<HALF_LIFE> {
+    // Evaluated when `new` is instantiated, since decaying by a zero half-life isn't defined.
+    const NONZERO_HALF_LIFE: () = assert!(HALF_LIFE > 0, "DecayScore half-life must be nonzero");
+
+    pub fn new(score: f32, last_ts: u32) -> Self {
+        let () = Self::NONZERO_HALF_LIFE;
+
+        Self { score, last_ts }
+    }
+
+    /// The score as of the most recent timestamp.
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    pub fn last_ts(&self) -> u32 {
+        self.last_ts
+    }
+
+    /// The score decayed to the given timestamp (timestamps before the last have no effect).
+    pub fn score_at(&self, timestamp: u32) -> f32 {
+        Self::decay(self.score, timestamp.saturating_sub(self.last_ts))
+    }
+
+    fn decay(score: f32, elapsed: u32) -> f32 {
+        (f64::from(score) * 0.5f64.powf(f64::from(elapsed) / f64::from(HALF_LIFE))) as f32
+    }
+}
+
+impl<const HALF_LIFE: u32> From<(f32, u32)> for DecayScore<HALF_LIFE> {
+    fn from(input: (f32, u32)) -> Self {
+        Self::new(input.0, input.1)
+    }
+}
+
+/// A single observation at the given timestamp.
+impl<const HALF_LIFE: u32> From<u32> for DecayScore<HALF_LIFE> {
+    fn from(input: u32) -> Self {
+        Self::new(1.0, input)
+    }
+}
+
+impl<const HALF_LIFE: u32> Add for DecayScore<HALF_LIFE> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        let last_ts = self.last_ts.max(other.last_ts);
+
+        Self {
+            score: self.score_at(last_ts) + other.score_at(last_ts),
+            last_ts,
+        }
+    }
+}
+
+impl<const HALF_LIFE: u32> From<DecayScore<HALF_LIFE>> for Vec<u8> {
+    fn from(input: DecayScore<HALF_LIFE>) -> Self {
+        let mut result = Vec::with_capacity(8);
+        result.extend_from_slice(&input.score.to_be_bytes());
+        result.extend_from_slice(&input.last_ts.to_be_bytes());
+        result
+    }
+}
+
+impl<const HALF_LIFE: u32> TryFrom<&[u8]> for DecayScore<HALF_LIFE> {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() == 8 {
+            Ok(Self::new(
+                f32::from_be_bytes(
+                    bytes[0..4]
+                        .try_into()
+                        .map_err(|_| Error::invalid_value(bytes))?,
+                ),
+                u32::from_be_bytes(
+                    bytes[4..8]
+                        .try_into()
+                        .map_err(|_| Error::invalid_value(bytes))?,
+                ),
+            ))
+        } else {
+            Err(Error::invalid_value(bytes))
+        }
+    }
+}
+
+impl<const HALF_LIFE: u32> Value for DecayScore<HALF_LIFE> {
+    const TYPE_TAG: &'static str = "DecayScore";
+
+    fn type_tag() -> Cow<'static, str> {
+        Cow::Owned(format!("{}/{}", Self::TYPE_TAG, HALF_LIFE))
+    }
+
+    fn byte_len_hint(&self) -> usize {
+        8
+    }
+
+    fn last_timestamp(&self) -> Option<u32> {
+        Some(self.last_ts)
+    }
+}
+
+impl<const HALF_LIFE: u32> Timestamps for DecayScore<HALF_LIFE> {}
+
+/// Represents arbitrary bytes where the most recent write replaces any previous value.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Blob(Vec<u8>);
+
+impl Blob {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from(input: Vec<u8>) -> Self {
+        Self(input)
+    }
+}
+
+/// The last writer wins, since merge operands are combined in the order they were written.
+impl Add for Blob {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        other
+    }
+}
+
+impl From<Blob> for Vec<u8> {
+    fn from(input: Blob) -> Self {
+        input.0
+    }
+}
+
+impl TryFrom<&[u8]> for Blob {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(bytes.to_vec()))
+    }
+}
+
+impl Value for Blob {
+    const TYPE_TAG: &'static str = "Blob";
+
+    fn byte_len_hint(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Represents a set of unsigned integers as a compressed bitmap.
+///
+/// This is an alternative to `Set64` that can be much more compact for large, dense sets.
+#[cfg(feature = "roaring")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoaringSet {
+    bitmap: roaring::RoaringTreemap,
+}
+
+#[cfg(feature = "roaring")]
+impl RoaringSet {
+    pub fn new(values: &[u64]) -> Self {
+        Self {
+            bitmap: values.iter().copied().collect(),
+        }
+    }
+
+    pub fn singleton(value: u64) -> Self {
+        Self::new(&[value])
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        self.bitmap.contains(value)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.bitmap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bitmap.iter()
+    }
+
+    pub fn into_inner(self) -> roaring::RoaringTreemap {
+        self.bitmap
+    }
+}
+
+#[cfg(feature = "roaring")]
+impl From<&[u64]> for RoaringSet {
+    fn from(input: &[u64]) -> Self {
+        Self::new(input)
+    }
+}
+
+#[cfg(feature = "roaring")]
+impl From<u64> for RoaringSet {
+    fn from(input: u64) -> Self {
+        Self::singleton(input)
+    }
+}
+
+#[cfg(feature = "roaring")]
+impl Add for RoaringSet {
+    type Output = Self;
+
+    // Merging is set union, which the bitmap exposes as bitwise or.
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            bitmap: self.bitmap | other.bitmap,
+        }
+    }
+}
+
+#[cfg(feature = "roaring")]
+impl From<RoaringSet> for Vec<u8> {
+    fn from(input: RoaringSet) -> Self {
+        let mut result = Vec::with_capacity(input.bitmap.serialized_size());
+        // Serializing into a vector can't fail.
+        input.bitmap.serialize_into(&mut result).unwrap();
+        result
+    }
+}
+
+#[cfg(feature = "roaring")]
+impl TryFrom<&[u8]> for RoaringSet {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bitmap = roaring::RoaringTreemap::deserialize_from(bytes)
+            .map_err(|_| Error::invalid_value(bytes))?;
+
+        Ok(Self { bitmap })
+    }
+}
+
+#[cfg(feature = "roaring")]
+impl Value for RoaringSet {
+    const TYPE_TAG: &'static str = "RoaringSet";
+
+    fn byte_len_hint(&self) -> usize {
+        self.bitmap.serialized_size()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bitmap.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clustered_ids() -> Vec<u64> {
+        (0..1000)
+            .map(|i| 1_500_000_000_000_000_000 + i * 37 + (i % 7))
+            .collect()
+    }
+
+    #[test]
+    fn set_into_iter() {
+        let set32 = Set32::new(&[3, 1, 2, 3]);
+        let mut by_reference = vec![];
+
+        for value in &set32 {
+            by_reference.push(*value);
+        }
+
+        assert_eq!(by_reference, vec![1, 2, 3]);
+        assert_eq!(set32.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let set64 = Set64::new(&[u64::MAX, 0, 1 << 40]);
+        let mut by_value = vec![];
+
+        for value in set64.clone() {
+            by_value.push(value);
+        }
+
+        assert_eq!(by_value, vec![0, 1 << 40, u64::MAX]);
+        assert_eq!((&set64).into_iter().max(), Some(&u64::MAX));
+    }
+
+    #[test]
+    fn set_intersect_difference() {
+        let set = Set64::new(&[1, 3, 5, 7, u64::MAX]);
+        let disjoint = Set64::new(&[0, 2, 4, 6]);
+        let overlapping = Set64::new(&[0, 3, 4, 7, 8, u64::MAX]);
+
+        assert_eq!(set.intersect(&disjoint), Set64::new(&[]));
+        assert_eq!(set.difference(&disjoint), set);
+        assert_eq!(set.intersect(&set), set);
+        assert_eq!(set.difference(&set), Set64::new(&[]));
+        assert_eq!(set.intersect(&overlapping), Set64::new(&[3, 7, u64::MAX]));
+        assert_eq!(set.difference(&overlapping), Set64::new(&[1, 5]));
+        assert_eq!(overlapping.difference(&set), Set64::new(&[0, 4, 8]));
+        assert_eq!(set.intersect(&Set64::new(&[])), Set64::new(&[]));
+        assert_eq!(Set64::new(&[]).difference(&set), Set64::new(&[]));
+
+        let set = Set32::new(&[10, 20, 30]);
+        let disjoint = Set32::new(&[15, 25]);
+        let overlapping = Set32::new(&[5, 20, 30, 40]);
+
+        assert_eq!(set.intersect(&disjoint), Set32::new(&[]));
+        assert_eq!(set.difference(&disjoint), set);
+        assert_eq!(set.intersect(&set), set);
+        assert_eq!(set.difference(&set), Set32::new(&[]));
+        assert_eq!(set.intersect(&overlapping), Set32::new(&[20, 30]));
+        assert_eq!(set.difference(&overlapping), Set32::new(&[10]));
+    }
+
+    #[test]
+    fn compact_set64_round_trip() {
+        let values = vec![0, 1, 127, 128, 300, 16_384, 1 << 40, u64::MAX - 1, u64::MAX];
+        let set = CompactSet64::new(&values);
+        let bytes: Vec<u8> = set.clone().into();
+
+        assert_eq!(CompactSet64::try_from(bytes.as_slice()).unwrap(), set);
+        assert_eq!(
+            CompactSet64::try_from(bytes.as_slice())
+                .unwrap()
+                .into_inner(),
+            values
+        );
+
+        let clustered = CompactSet64::new(&clustered_ids());
+        let bytes: Vec<u8> = clustered.clone().into();
+
+        assert_eq!(CompactSet64::try_from(bytes.as_slice()).unwrap(), clustered);
+    }
+
+    #[test]
+    fn compact_set64_empty() {
+        let bytes: Vec<u8> = CompactSet64::new(&[]).into();
+
+        assert!(bytes.is_empty());
+        assert_eq!(
+            CompactSet64::try_from(bytes.as_slice()).unwrap(),
+            CompactSet64::new(&[])
+        );
+    }
+
+    #[test]
+    fn compact_set64_invalid() {
+        // Truncated varint.
+        assert!(CompactSet64::try_from(&[0x80u8][..]).is_err());
+        // Zero delta (duplicate value).
+        assert!(CompactSet64::try_from(&[0x01u8, 0x00][..]).is_err());
+        // Overlong varint.
+        assert!(CompactSet64::try_from(&[0xffu8; 11][..]).is_err());
+        // Delta overflow.
+        assert!(CompactSet64::try_from(
+            &[0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x01][..]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn compact_set64_size() {
+        let ids = clustered_ids();
+        let compact: Vec<u8> = CompactSet64::new(&ids).into();
+        let naive: Vec<u8> = Set64::new(&ids).into();
+
+        assert!(compact.len() * 10 < naive.len() * 4);
+    }
+
+    #[test]
+    fn compact_set64_add() {
+        let merged = CompactSet64::new(&[1, 5, 1 << 40]) + CompactSet64::new(&[2, 5, 7]);
+
+        assert_eq!(merged.values(), &[1, 2, 5, 7, 1 << 40]);
+
+        let existing: Vec<u8> = CompactSet64::new(&[10, 20]).into();
+        let operands: Vec<Vec<u8>> = vec![CompactSet64::singleton(15).into()];
+
+        let merged = CompactSet64::merge(
+            Some(existing.as_slice()),
+            operands.iter().map(|bytes| bytes.as_slice()),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            CompactSet64::try_from(merged.as_slice()).unwrap(),
+            CompactSet64::new(&[10, 15, 20])
+        );
+    }
+
+    #[test]
+    fn histogram_add() {
+        let first = Histogram::from((10, 2)) + Histogram::singleton(12);
+        let second = Histogram::from((10, 3)) + Histogram::from((11, 1)) + Histogram::from(20);
+        let merged = first + second;
+
+        assert_eq!(merged.count(10), 5);
+        assert_eq!(merged.count(11), 1);
+        assert_eq!(merged.count(12), 1);
+        assert_eq!(merged.count(13), 0);
+        assert_eq!(merged.count(20), 1);
+        assert_eq!(merged.total(), 8);
+
+        let saturated = Histogram::from((0, u32::MAX)) + Histogram::from((0, 1));
+
+        assert_eq!(saturated.count(0), u32::MAX);
+    }
+
+    #[test]
+    fn histogram_round_trip() {
+        let histogram = Histogram::from((3, 7)) + Histogram::from((1, 2)) + Histogram::from((2, 1));
+        let bytes: Vec<u8> = histogram.clone().into();
+
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(&bytes[0..8], &[0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!(Histogram::try_from(bytes.as_slice()).unwrap(), histogram);
+
+        assert!(Histogram::try_from(&bytes[0..7]).is_err());
+
+        let mut unsorted = bytes[8..16].to_vec();
+        unsorted.extend_from_slice(&bytes[0..8]);
+
+        assert!(Histogram::try_from(unsorted.as_slice()).is_err());
+    }
+
+    #[test]
+    fn set64_decode_sorted() {
+        let set = Set64::new(&[5, 1, u64::MAX, 3, 1, 0]);
+        let bytes: Vec<u8> = set.clone().into();
+        let decoded = Set64::try_from(bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded, set);
+        assert_eq!(decoded.values(), &[0, 1, 3, 5, u64::MAX]);
+    }
+
+    #[test]
+    fn set64_validate_unsorted() {
+        let bytes = [3u64, 1, 2]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect::<Vec<_>>();
+        let duplicated = [1u64, 1]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        assert!(Set64::validate(&bytes).is_err());
+        assert!(Set64::validate(&duplicated).is_err());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Set64 values must be sorted and unique")]
+    fn set64_from_sorted_unchecked_unsorted() {
+        Set64::from_sorted_unchecked(vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn range32_from_timestamps() {
+        assert_eq!(Range32::from_timestamps(vec![]), None);
+        assert_eq!(
+            Range32::from_timestamps(vec![10]),
+            Some(Range32::new(10, 10))
+        );
+        assert_eq!(
+            Range32::from_timestamps(vec![10, 3, u32::MAX, 7]),
+            Some(Range32::new(3, u32::MAX))
+        );
+    }
+
+    #[test]
+    fn serialized_len() {
+        let range = Range32::new(3, 10);
+        assert_eq!(range.serialized_len(), Vec::<u8>::from(range).len());
+
+        for values in [&[][..], &[1], &[3, 1, 2, u32::MAX]] {
+            let set = Set32::new(values);
+            assert_eq!(set.serialized_len(), Vec::<u8>::from(set).len());
+
+            let values = values.iter().map(|value| *value as u64).collect::<Vec<_>>();
+            let set = Set64::new(&values);
+            assert_eq!(set.serialized_len(), Vec::<u8>::from(set).len());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let range = Range32::new(3, 10);
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(json, r#"{"first":3,"last":10}"#);
+        assert_eq!(serde_json::from_str::<Range32>(&json).unwrap(), range);
+
+        let set = Set32::new(&[3, 1, 2]);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, "[1,2,3]");
+        assert_eq!(serde_json::from_str::<Set32>(&json).unwrap(), set);
+
+        let set = Set64::new(&[u64::MAX, 0]);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, format!("[0,{}]", u64::MAX));
+        assert_eq!(serde_json::from_str::<Set64>(&json).unwrap(), set);
+
+        assert_eq!(
+            serde_json::from_str::<Set64>("[3,1,3,2]").unwrap(),
+            Set64::new(&[1, 2, 3])
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn to_datetime_range() {
+        use chrono::{TimeZone, Utc};
+
+        let range = Range32::new(1_600_000_000, 1_600_000_060);
+
+        assert_eq!(
+            range.to_datetime_range(TimeUnit::Seconds),
+            (
+                Utc.with_ymd_and_hms(2020, 9, 13, 12, 26, 40).unwrap(),
+                Utc.with_ymd_and_hms(2020, 9, 13, 12, 27, 40).unwrap()
+            )
+        );
+
+        let (first, last) = Range32::new(1_500, 86_400_000).to_datetime_range(TimeUnit::Millis);
+
+        assert_eq!(first.timestamp_millis(), 1_500);
+        assert_eq!(last, Utc.with_ymd_and_hms(1970, 1, 2, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn range32_span_and_midpoint() {
+        assert_eq!(Range32::new(10, 10).span(), 0);
+        assert_eq!(Range32::new(10, 10).midpoint(), 10);
+        assert_eq!(Range32::new(10, 15).span(), 5);
+        assert_eq!(Range32::new(10, 15).midpoint(), 12);
+        assert_eq!(Range32::new(0, u32::MAX).span(), u32::MAX);
+        assert_eq!(
+            Range32::new(u32::MAX - 2, u32::MAX).midpoint(),
+            u32::MAX - 1
+        );
+        assert_eq!(Range32::new(15, 10).span(), 0);
+    }
+
+    #[test]
+    fn range32_is_expired() {
+        assert!(Range32::new(1, 9).is_expired(10));
+        assert!(!Range32::new(1, 10).is_expired(10));
+        assert!(!Range32::new(11, 20).is_expired(10));
+        assert!(!Set64::new(&[1]).is_expired(10));
+    }
+
+    #[test]
+    fn set32_is_expired() {
+        assert!(Set32::new(&[1, 5, 9]).is_expired(10));
+        assert!(!Set32::new(&[1, 5, 10]).is_expired(10));
+        assert!(!Set32::new(&[]).is_expired(10));
+    }
+
+    #[test]
+    fn set32_expire() {
+        assert_eq!(
+            Set32::new(&[1, 5, 10, 12]).expire(10),
+            Set32::new(&[10, 12])
+        );
+        assert_eq!(Set32::new(&[1, 5, 9]).expire(10), Set32::new(&[]));
+        assert_eq!(Range32::new(1, 12).expire(10), Range32::new(1, 12));
+    }
+
+    #[test]
+    fn byte_len_hint() {
+        fn check<V: Value + Clone>(value: V) {
+            assert_eq!(value.byte_len_hint(), value.clone().into().len());
+        }
+
+        check(Range32::new(3, 10));
+        check(RangeCount::new(3, 10, 4));
+        check(Ranges32::<10>::new(&[(1, 2), (20, 30)]));
+        check(Set32::new(&[]));
+        check(Set32::new(&[3, 1, 2]));
+        check(Set64::new(&[u64::MAX, 0]));
+        check(DiffSet64::addition(1) + DiffSet64::removal(2));
+        check(CompactSet64::new(&[]));
+        check(CompactSet64::new(&[0, 127, 128, 300, 1 << 40, u64::MAX]));
+        check(Histogram::from((1, 2)) + Histogram::singleton(5));
+        check(Sum64::from(10u64));
+        check(SaturatingSum32::new(10));
+        check(Blob::new(b"foo".to_vec()));
+        check(DecayScore::<10>::new(1.5, 10));
+
+        #[cfg(feature = "roaring")]
+        check(RoaringSet::new(&[1, 2, u64::MAX]));
+    }
+
+    #[test]
+    fn is_valid_len() {
+        assert!(Range32::is_valid_len(&[0; 8]));
+        assert!(!Range32::is_valid_len(&[]));
+        assert!(!Range32::is_valid_len(&[0; 16]));
+
+        assert!(Set32::is_valid_len(&[]));
+        assert!(Set32::is_valid_len(&[0; 12]));
+        assert!(!Set32::is_valid_len(&[0; 6]));
+
+        assert!(Set64::is_valid_len(&[]));
+        assert!(Set64::is_valid_len(&[0; 16]));
+        assert!(!Set64::is_valid_len(&[0; 12]));
+        assert!(Set64::try_from(&[0u8; 12][..]).is_err());
+    }
+
+    #[test]
+    fn diff_set64() {
+        let operands: Vec<Vec<u8>> = vec![
+            DiffSet64::addition(3).into(),
+            DiffSet64::addition(1).into(),
+            DiffSet64::addition(2).into(),
+            DiffSet64::removal(2).into(),
+        ];
+        let merged = DiffSet64::merge(None, operands.iter().map(|bytes| bytes.as_slice()))
+            .unwrap()
+            .unwrap();
+        let value = DiffSet64::try_from(merged.as_slice()).unwrap();
+
+        assert_eq!(value.values(), &[1, 3]);
+        assert_eq!(value.removed(), &[2]);
+
+        // Combining operands before applying them to the existing value gives the same result.
+        let partial = DiffSet64::merge(None, operands[2..].iter().map(|bytes| bytes.as_slice()))
+            .unwrap()
+            .unwrap();
+        let existing: Vec<u8> = (DiffSet64::addition(3) + DiffSet64::addition(1)).into();
+        let merged = DiffSet64::merge(Some(&existing), std::iter::once(partial.as_slice()))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(DiffSet64::try_from(merged.as_slice()).unwrap(), value);
+
+        let readded = value + DiffSet64::addition(2);
+
+        assert_eq!(readded.values(), &[1, 2, 3]);
+        assert!(readded.removed().is_empty());
+    }
+
+    #[test]
+    fn diff_set64_invalid() {
+        assert!(DiffSet64::try_from(&[0u8; 8][..]).is_err());
+        assert!(DiffSet64::try_from(&[2u8, 0, 0, 0, 0, 0, 0, 0, 1][..]).is_err());
+        assert_eq!(DiffSet64::try_from(&[][..]).unwrap(), DiffSet64::default());
+    }
+
+    #[test]
+    fn type_tag() {
+        assert_eq!(Set32::type_tag(), "Set32");
+        assert_eq!(Ranges32::<10>::type_tag(), "Ranges32/10");
+        assert_eq!(DecayScore::<60>::type_tag(), "DecayScore/60");
+    }
+
+    #[test]
+    fn last_timestamp() {
+        assert_eq!(Range32::new(1, 101).last_timestamp(), Some(101));
+        assert_eq!(Set32::new(&[50, 3, 7]).last_timestamp(), Some(50));
+        assert_eq!(Set32::new(&[]).last_timestamp(), None);
+        assert_eq!(
+            Ranges32::<10>::new(&[(1, 5), (30, 40)]).last_timestamp(),
+            Some(40)
+        );
+        assert_eq!(DecayScore::<10>::new(1.0, 20).last_timestamp(), Some(20));
+        assert_eq!(Sum64::from(10u64).last_timestamp(), None);
+    }
+
+    #[test]
+    fn value_is_empty() {
+        assert!(Value::is_empty(&Set32::new(&[])));
+        assert!(!Value::is_empty(&Set32::singleton(1)));
+        assert!(Value::is_empty(&Set64::new(&[])));
+        assert!(Value::is_empty(&CompactSet64::new(&[])));
+        assert!(Value::is_empty(&DiffSet64::default()));
+        assert!(!Value::is_empty(&DiffSet64::removal(1)));
+        assert!(Value::is_empty(&Histogram::new()));
+        assert!(!Value::is_empty(&Range32::singleton(0)));
+    }
+
+    #[test]
+    fn ranges32() {
+        let timestamps = [100u32, 5, 20, 10, 95, 200];
+        let operands: Vec<Vec<u8>> = timestamps
+            .iter()
+            .map(|timestamp| Ranges32::<10>::from(*timestamp).into())
+            .collect();
+        let merged = Ranges32::<10>::merge(None, operands.iter().map(|bytes| bytes.as_slice()))
+            .unwrap()
+            .unwrap();
+        let value = Ranges32::<10>::try_from(merged.as_slice()).unwrap();
+
+        assert_eq!(value.ranges(), &[(5, 20), (95, 100), (200, 200)]);
+        assert_eq!(
+            (value + Ranges32::new(&[(30, 90)])).ranges(),
+            &[(5, 100), (200, 200)]
+        );
+        assert_eq!(
+            Ranges32::<0>::new(&[(3, 4), (1, 2), (4, 4)]).ranges(),
+            &[(1, 2), (3, 4)]
+        );
+        assert_eq!(
+            Ranges32::<{ u32::MAX }>::new(&[(1, 2), (u32::MAX, u32::MAX)]).ranges(),
+            &[(1, u32::MAX)]
+        );
+
+        let mut overlapping: Vec<u8> = Ranges32::<0>::new(&[(1, 5)]).into();
+        overlapping.extend(Vec::from(Ranges32::<0>::new(&[(5, 6)])));
+
+        assert!(Ranges32::<0>::try_from(overlapping.as_slice()).is_err());
+        assert!(Ranges32::<0>::try_from(&[0u8; 4][..]).is_err());
+    }
+
+    #[test]
+    fn range_count() {
+        let observations = [(20u32, 1u64), (10, 1), (15, 1)];
+        let operands: Vec<Vec<u8>> = observations
+            .iter()
+            .map(|observation| RangeCount::from(*observation).into())
+            .collect();
+        let merged = RangeCount::merge(None, operands.iter().map(|bytes| bytes.as_slice()))
+            .unwrap()
+            .unwrap();
+        let value = RangeCount::try_from(merged.as_slice()).unwrap();
+
+        assert_eq!(value, RangeCount::new(10, 20, 3));
+        assert_eq!(value.first(), 10);
+        assert_eq!(value.last(), 20);
+        assert_eq!(value.count(), 3);
+        assert_eq!(merged.len(), 16);
+        assert!(RangeCount::try_from(&merged[0..8]).is_err());
+    }
+
+    #[test]
+    fn sum64() {
+        let inputs = [u32::MAX, u32::MAX, 3, u32::MAX];
+        let sum = inputs
+            .iter()
+            .map(|input| Sum64::from(*input))
+            .fold(Sum64::default(), |acc, next| acc + next);
+
+        assert_eq!(sum.value(), 3 * u64::from(u32::MAX) + 3);
+
+        let bytes: Vec<u8> = sum.into();
+
+        assert_eq!(Sum64::try_from(bytes.as_slice()).unwrap(), sum);
+        assert!(Sum64::try_from(&bytes[0..4]).is_err());
+
+        let operands: Vec<Vec<u8>> = inputs
+            .iter()
+            .map(|input| Sum64::from(*input).into())
+            .collect();
+        let merged = Sum64::merge(None, operands.iter().map(|bytes| bytes.as_slice()))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(Sum64::try_from(merged.as_slice()).unwrap(), sum);
+    }
+
+    #[test]
+    fn decay_score() {
+        let first = DecayScore::<10>::from(100);
+        let second = DecayScore::<10>::from(110);
+
+        assert_eq!(first + second, DecayScore::new(1.5, 110));
+        assert_eq!(second + first, DecayScore::new(1.5, 110));
+        assert_eq!((first + second).score_at(120), 0.75);
+        assert_eq!((first + second).score_at(0), 1.5);
+        assert_eq!(
+            DecayScore::<10>::new(4.0, 0) + DecayScore::new(1.0, 30),
+            DecayScore::new(1.5, 30)
+        );
+
+        let bytes: Vec<u8> = DecayScore::<10>::new(1.5, 110).into();
+
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(
+            DecayScore::<10>::try_from(bytes.as_slice()).unwrap(),
+            DecayScore::new(1.5, 110)
+        );
+        assert!(DecayScore::<10>::try_from(&bytes[0..4]).is_err());
+    }
+
+    #[test]
+    fn validate() {
+        fn check<V: Value>(value: V) {
+            let bytes: Vec<u8> = value.into();
+
+            assert!(V::validate(&bytes).is_ok());
+            assert!(V::validate(&bytes[..bytes.len() - 1]).is_err());
+        }
+
+        check(Range32::new(1, 101));
+        check(Set32::new(&[1, 2, 3]));
+        check(Set64::new(&[1, 2, 3]));
+        check(Sum64::from(10u64));
+
+        assert!(Range32::validate(&[]).is_err());
+        assert!(Range32::validate(&[0; 16]).is_err());
+        assert!(Set32::validate(&[]).is_ok());
+        assert!(Set32::validate(&[0; 6]).is_err());
+        assert!(Set64::validate(&[0; 12]).is_err());
+    }
+
+    #[test]
+    fn blob() {
+        let blob =
+            Blob::new(b"foo".to_vec()) + Blob::new(b"".to_vec()) + Blob::new(b"bar".to_vec());
+
+        assert_eq!(blob.as_bytes(), b"bar");
+
+        let bytes: Vec<u8> = blob.clone().into();
+
+        assert_eq!(Blob::try_from(bytes.as_slice()).unwrap(), blob);
+
+        let merged = Blob::merge(Some(b"foo"), vec![&b"bar"[..], b"", b"qux"].into_iter()).unwrap();
+
+        assert_eq!(merged, Some(b"qux".to_vec()));
+    }
+
+    #[test]
+    fn saturating_sum32() {
+        let sum = SaturatingSum32::from(u32::MAX - 1) + SaturatingSum32::from(1);
+
+        assert_eq!(sum.value(), u32::MAX);
+        assert_eq!((sum + SaturatingSum32::from(10)).value(), u32::MAX);
+
+        let bytes: Vec<u8> = SaturatingSum32::new(12345).into();
+
+        assert_eq!(
+            SaturatingSum32::try_from(bytes.as_slice()).unwrap().value(),
+            12345
+        );
+        assert!(SaturatingSum32::try_from(&[0u8; 8][..]).is_err());
+    }
+
+    #[cfg(feature = "roaring")]
+    #[test]
+    fn roaring_set_membership() {
+        let values = [1, 5, 5, 1 << 40, 23, u64::MAX];
+        let roaring_set = RoaringSet::new(&values);
+        let set64 = Set64::new(&values);
+
+        assert_eq!(roaring_set.len(), set64.values().len() as u64);
+
+        for value in [0, 1, 5, 6, 23, 1 << 40, (1 << 40) + 1, u64::MAX] {
+            assert_eq!(roaring_set.contains(value), set64.values().contains(&value));
+        }
+    }
+
+    #[cfg(feature = "roaring")]
+    #[test]
+    fn roaring_set_union() {
+        let left = [1, 2, 3, 1 << 33];
+        let right = [3, 4, 1 << 33, 1 << 50];
+
+        let roaring_union = RoaringSet::new(&left) + RoaringSet::new(&right);
+        let set64_union = Set64::new(&left) + Set64::new(&right);
+
+        assert_eq!(
+            roaring_union.iter().collect::<Vec<_>>(),
+            set64_union.into_inner()
+        );
+    }
+
+    #[cfg(feature = "roaring")]
+    #[test]
+    fn roaring_set_round_trip() {
+        let set = RoaringSet::new(&[0, 10, 1 << 40, u64::MAX]);
+        let bytes: Vec<u8> = set.clone().into();
+
+        assert_eq!(RoaringSet::try_from(bytes.as_slice()).unwrap(), set);
+        assert!(RoaringSet::try_from(&[1u8, 2, 3][..]).is_err());
+    }
+
+    #[cfg(feature = "roaring")]
+    #[test]
+    fn roaring_set_merge() {
+        let existing: Vec<u8> = RoaringSet::new(&[1, 2]).into();
+        let operands: Vec<Vec<u8>> = vec![
+            RoaringSet::singleton(3).into(),
+            RoaringSet::new(&[2, 1 << 40]).into(),
+        ];
+
+        let merged = RoaringSet::merge(
+            Some(existing.as_slice()),
+            operands.iter().map(|bytes| bytes.as_slice()),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            RoaringSet::try_from(merged.as_slice()).unwrap(),
+            RoaringSet::new(&[1, 2, 3, 1 << 40])
+        );
     }
 }